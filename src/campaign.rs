@@ -0,0 +1,325 @@
+use std::{
+    fmt::Write as FmtWrite,
+    fs::create_dir_all,
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::{Path, PathBuf},
+};
+
+use rand::{RngCore, SeedableRng};
+
+use crate::{
+    error::MetamorphicError,
+    transformation_sequence::{
+        TransformationSequenceConfig, load_program, run_transformation_sequence,
+    },
+    transformations::{
+        transformation_manager::TransformationWeights, transformation_types::TransformationTypes,
+    },
+};
+
+/// Everything needed to run a campaign over a corpus of rule files
+pub struct CampaignConfig {
+    /// Rule file or folder that is searched recursively for rule files
+    pub corpus: PathBuf,
+    /// Seed from which the seeds of the single runs are derived
+    pub campaign_seed: u64,
+    /// Number of seeds per rule file and transformation type
+    pub seeds_per_program: u32,
+    /// Number of transformations per run
+    pub length: u32,
+    /// The transformation types each rule file and seed is run with
+    pub transformation_types: Vec<TransformationTypes>,
+    /// Relative weights with which the applicable transformations are chosen
+    pub weights: TransformationWeights,
+    /// Folder the result folders and the summary are written to
+    pub output_folder: PathBuf,
+    /// Reduce every failing sequence to a minimal failing one
+    pub reduce: bool,
+    /// Cross-check the ADG against the program after every transformation
+    pub validate: bool,
+    /// Load the rows of imported csv and tsv files, so they can be transformed
+    pub load_imports: bool,
+}
+
+/// Outcome of a single run of a campaign
+pub enum CampaignOutcome {
+    /// The metamorphic relation holds
+    Pass,
+    /// The metamorphic relation is violated
+    Fail,
+    /// Nemo panicked, or failed on the transformed program
+    Crash(String),
+    /// The program could not be transformed or evaluated
+    Skipped(String),
+    /// The harness panicked or its ADG drifted from the program
+    HarnessBug(String),
+}
+impl CampaignOutcome {
+    fn name(&self) -> &'static str {
+        match self {
+            CampaignOutcome::Pass => "pass",
+            CampaignOutcome::Fail => "fail",
+            CampaignOutcome::Crash(_) => "crash",
+            CampaignOutcome::Skipped(_) => "skipped",
+            CampaignOutcome::HarnessBug(_) => "harness-bug",
+        }
+    }
+    fn message(&self) -> &str {
+        match self {
+            CampaignOutcome::Pass | CampaignOutcome::Fail => "",
+            CampaignOutcome::Crash(message)
+            | CampaignOutcome::Skipped(message)
+            | CampaignOutcome::HarnessBug(message) => message,
+        }
+    }
+}
+
+/// A single run of a campaign
+pub struct CampaignRun {
+    pub program: PathBuf,
+    pub seed: Option<u64>,
+    pub transformation_type: Option<TransformationTypes>,
+    pub result_folder: Option<PathBuf>,
+    pub outcome: CampaignOutcome,
+}
+
+/// Run a transformation sequence for every (rule file, seed, transformation type)
+/// of the campaign and write a summary of the outcomes.
+pub fn run_campaign(config: &CampaignConfig) -> Result<Vec<CampaignRun>, MetamorphicError> {
+    let programs: Vec<PathBuf> = collect_rule_files(&config.corpus)?;
+    println!(
+        "Campaign over {} rule files with campaign seed {}",
+        programs.len(),
+        config.campaign_seed
+    );
+    // Every rule file is run with the same derived seeds
+    let mut campaign_rng = rand_chacha::ChaCha8Rng::seed_from_u64(config.campaign_seed);
+    let seeds: Vec<u64> = (0..config.seeds_per_program)
+        .map(|_| campaign_rng.next_u64())
+        .collect();
+
+    if create_dir_all(&config.output_folder).is_err() {
+        return Err(MetamorphicError::Write(config.output_folder.clone()));
+    }
+
+    let mut runs: Vec<CampaignRun> = Vec::new();
+    for program in programs {
+        // Programs nemo can't parse are skipped once instead of per run
+        // Parsing only runs nemo, so a panic is a crash of nemo
+        let parse_outcome = match catch_crash(|| load_program(&program)) {
+            Ok(Ok(_)) => None,
+            Ok(Err(error)) => Some(CampaignOutcome::Skipped(error.to_string())),
+            Err(message) => Some(CampaignOutcome::Crash(message)),
+        };
+        if let Some(outcome) = parse_outcome {
+            println!("{}: {}", program.display(), outcome.name());
+            runs.push(CampaignRun {
+                program,
+                seed: None,
+                transformation_type: None,
+                result_folder: None,
+                outcome,
+            });
+            continue;
+        }
+        let program_folder = config
+            .output_folder
+            .join(result_folder_name(&config.corpus, &program));
+        for seed in &seeds {
+            for transformation_type in &config.transformation_types {
+                let result_folder = program_folder
+                    .join(format!("seed_{seed}"))
+                    .join(transformation_type.to_string());
+                let sequence_config = TransformationSequenceConfig {
+                    input_file: program.clone(),
+                    seed: *seed,
+                    length: config.length,
+                    transformation_type: transformation_type.clone(),
+                    weights: config.weights.clone(),
+                    output_folder: result_folder.clone(),
+                    reduce: config.reduce,
+                    validate: config.validate,
+                    load_imports: config.load_imports,
+                };
+                let outcome = run_isolated(&sequence_config);
+                println!(
+                    "{} seed {seed} {transformation_type}: {}",
+                    program.display(),
+                    outcome.name()
+                );
+                runs.push(CampaignRun {
+                    program: program.clone(),
+                    seed: Some(*seed),
+                    transformation_type: Some(transformation_type.clone()),
+                    result_folder: Some(result_folder),
+                    outcome,
+                });
+            }
+        }
+    }
+
+    write_summary(&config.output_folder, &runs)?;
+    Ok(runs)
+}
+
+/// Run one transformation sequence. Panics of nemo's evaluation are already
+/// turned into errors, so the remaining ones are bugs of the harness.
+fn run_isolated(config: &TransformationSequenceConfig) -> CampaignOutcome {
+    match catch_crash(|| run_transformation_sequence(config)) {
+        Ok(Ok(oracle_report)) => {
+            if oracle_report.passed() {
+                CampaignOutcome::Pass
+            } else {
+                CampaignOutcome::Fail
+            }
+        }
+        // Nemo failing on the transformed program is a crash of the engine
+        Ok(Err(
+            error @ (MetamorphicError::EngineFailure { .. } | MetamorphicError::EnginePanic { .. }),
+        )) => CampaignOutcome::Crash(error.to_string()),
+        Ok(Err(
+            error @ (MetamorphicError::AdgDrift { .. } | MetamorphicError::FailedUpdate { .. }),
+        )) => CampaignOutcome::HarnessBug(error.to_string()),
+        Ok(Err(error)) => CampaignOutcome::Skipped(error.to_string()),
+        Err(message) => CampaignOutcome::HarnessBug(format!("The harness panicked: {message}")),
+    }
+}
+
+/// Call `f`, returning the panic message if it panics
+pub fn catch_crash<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    // Evaluation changes into the import folder, a panic must not leave us there
+    let working_folder = std::env::current_dir().ok();
+    let result = catch_unwind(AssertUnwindSafe(f));
+    if let Some(working_folder) = working_folder {
+        let _ = std::env::set_current_dir(working_folder);
+    }
+    result.map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            String::from("Unknown panic")
+        }
+    })
+}
+
+/// Find all rule files in the corpus, sorted to keep campaigns reproducible
+fn collect_rule_files(corpus: &Path) -> Result<Vec<PathBuf>, MetamorphicError> {
+    if corpus.is_file() {
+        return Ok(vec![corpus.to_path_buf()]);
+    }
+    let mut rule_files: Vec<PathBuf> = Vec::new();
+    let mut folders: Vec<PathBuf> = vec![corpus.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        let entries = match std::fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(_) => return Err(MetamorphicError::ReadFile(folder)),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                folders.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "rls") {
+                rule_files.push(path);
+            }
+        }
+    }
+    rule_files.sort();
+    Ok(rule_files)
+}
+
+/// Name of the result folder of a rule file: its path relative
+/// to the corpus, with the folder separators replaced.
+fn result_folder_name(corpus: &Path, program: &Path) -> String {
+    let relative = program.strip_prefix(corpus).unwrap_or(program);
+    let relative = relative.with_extension("");
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if components.is_empty() {
+        String::from("program")
+    } else {
+        components.join("__")
+    }
+}
+
+/// Write `summary.txt` with the counts and noteworthy runs
+/// and `results.csv` with one line per run.
+fn write_summary(folder: &Path, runs: &[CampaignRun]) -> Result<(), MetamorphicError> {
+    let count = |name: &str| runs.iter().filter(|run| run.outcome.name() == name).count();
+    let mut summary = String::new();
+    let _ = writeln!(summary, "Runs: {}", runs.len());
+    let _ = writeln!(summary, "Passed: {}", count("pass"));
+    let _ = writeln!(summary, "Failed: {}", count("fail"));
+    let _ = writeln!(summary, "Crashed: {}", count("crash"));
+    let _ = writeln!(summary, "Skipped: {}", count("skipped"));
+    let _ = writeln!(summary, "Harness bugs: {}", count("harness-bug"));
+    for (title, name) in [
+        ("Failures", "fail"),
+        ("Crashes", "crash"),
+        ("Skipped", "skipped"),
+        ("Harness bugs", "harness-bug"),
+    ] {
+        if count(name) == 0 {
+            continue;
+        }
+        let _ = writeln!(summary, "\n{title}:");
+        for run in runs.iter().filter(|run| run.outcome.name() == name) {
+            let _ = writeln!(summary, "    {}", describe_run(run));
+        }
+    }
+
+    let mut results = String::from("program,seed,type,outcome,folder,message\n");
+    for run in runs {
+        let _ = writeln!(
+            results,
+            "{},{},{},{},{},{}",
+            csv_field(&run.program.to_string_lossy()),
+            run.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+            run.transformation_type
+                .as_ref()
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            run.outcome.name(),
+            csv_field(
+                &run.result_folder
+                    .as_ref()
+                    .map(|folder| folder.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            ),
+            csv_field(run.outcome.message()),
+        );
+    }
+
+    print!("{summary}");
+    if std::fs::write(folder.join("summary.txt"), summary).is_err()
+        || std::fs::write(folder.join("results.csv"), results).is_err()
+    {
+        return Err(MetamorphicError::Write(folder.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn describe_run(run: &CampaignRun) -> String {
+    let mut description = run.program.display().to_string();
+    if let Some(seed) = run.seed {
+        description += &format!(" seed {seed}");
+    }
+    if let Some(transformation_type) = &run.transformation_type {
+        description += &format!(" {transformation_type}");
+    }
+    if let Some(folder) = &run.result_folder {
+        description += &format!(" ({})", folder.display());
+    }
+    if !run.outcome.message().is_empty() {
+        description += &format!(": {}", run.outcome.message());
+    }
+    description
+}
+
+/// Quote a csv field
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\"").replace('\n', " "))
+}
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::transformations::{
+    transformation_manager::TransformationWeights, transformation_types::TransformationTypes,
+};
+
+/// Metamorphic testing of the nemo rule engine
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Apply a random sequence of metamorphic transformations to rule files
+    Transform(TransformArgs),
+    /// Evaluate the input and output program of a transformation sequence and check the metamorphic relation
+    Check(CheckArgs),
+    /// Run many transformation sequences over a corpus of rule files
+    Campaign(CampaignArgs),
+    /// Rebuild the output program of a transformation sequence from its trace
+    Replay(ReplayArgs),
+}
+
+#[derive(Args)]
+pub struct TransformArgs {
+    /// The rule file(s) to transform
+    #[arg(required = true)]
+    pub input: Vec<PathBuf>,
+    /// Seed of the random number generator
+    #[arg(short, long, default_value_t = 42)]
+    pub seed: u64,
+    /// Number of transformations in the sequence
+    #[arg(short = 'n', long, default_value_t = 32)]
+    pub length: u32,
+    /// Metamorphic relation the transformed program has to the input program (equ, exp, con)
+    #[arg(short = 't', long = "type", default_value = "con")]
+    pub transformation_type: TransformationTypes,
+    /// Relative weights of the transformations as kind=weight pairs, e.g. add-rule-edge=3,remove-fact=0.
+    /// Transformations that are not mentioned keep weight 1.
+    #[arg(short, long, default_value_t = TransformationWeights::default())]
+    pub weights: TransformationWeights,
+    /// Folder the input and output programs are written to.
+    /// With several input files, each one gets a subfolder named after the file.
+    #[arg(short, long, default_value = "Transformation Sequence 1")]
+    pub output: PathBuf,
+    /// If the metamorphic relation is violated, reduce the sequence to a minimal failing one
+    #[arg(long)]
+    pub reduce: bool,
+    /// Cross-check the ADG against the program after every transformation
+    /// and stop at the first step that makes them drift apart
+    #[arg(long)]
+    pub validate: bool,
+    /// Load the rows of imported csv and tsv files, so transformations can add and remove rows.
+    /// Changed files are written to a `data` folder next to the output program.
+    #[arg(long)]
+    pub load_imports: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Folder of the transformation sequence, containing the `input` and `output` folders
+    pub folder: PathBuf,
+    /// Metamorphic relation the output program should have to the input program (equ, exp, con)
+    #[arg(short = 't', long = "type", default_value = "con")]
+    pub transformation_type: TransformationTypes,
+    /// Folder that relative imports of the programs are resolved against
+    #[arg(short, long, default_value = ".")]
+    pub import_folder: PathBuf,
+}
+
+#[derive(Args)]
+pub struct CampaignArgs {
+    /// Rule file or folder that is searched recursively for rule files
+    pub corpus: PathBuf,
+    /// Seed from which the seeds of the single runs are derived
+    #[arg(short, long, default_value_t = 42)]
+    pub seed: u64,
+    /// Number of seeds per rule file and transformation type
+    #[arg(long, default_value_t = 10)]
+    pub seeds: u32,
+    /// Number of transformations in each sequence
+    #[arg(short = 'n', long, default_value_t = 32)]
+    pub length: u32,
+    /// Metamorphic relations to run every rule file and seed with (equ, exp, con)
+    #[arg(
+        short = 't',
+        long = "types",
+        value_delimiter = ',',
+        default_value = "equ,exp,con"
+    )]
+    pub transformation_types: Vec<TransformationTypes>,
+    /// Relative weights of the transformations as kind=weight pairs, e.g. add-rule-edge=3,remove-fact=0.
+    /// Transformations that are not mentioned keep weight 1.
+    #[arg(short, long, default_value_t = TransformationWeights::default())]
+    pub weights: TransformationWeights,
+    /// Folder the results and the summary are written to
+    #[arg(short, long, default_value = "Campaign")]
+    pub output: PathBuf,
+    /// Reduce every failing sequence to a minimal failing one
+    #[arg(long)]
+    pub reduce: bool,
+    /// Cross-check the ADG against the program after every transformation
+    /// and stop at the first step that makes them drift apart
+    #[arg(long)]
+    pub validate: bool,
+    /// Load the rows of imported csv and tsv files, so transformations can add and remove rows.
+    /// Changed files are written to a `data` folder next to the output program.
+    #[arg(long)]
+    pub load_imports: bool,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// The `trace.json` of the transformation sequence
+    pub trace: PathBuf,
+    /// Use this rule file instead of the input file stored in the trace
+    #[arg(short, long)]
+    pub input: Option<PathBuf>,
+    /// Folder the input and output programs are written to
+    #[arg(short, long, default_value = "Replay")]
+    pub output: PathBuf,
+    /// If the metamorphic relation is violated, reduce the sequence to a minimal failing one
+    #[arg(long)]
+    pub reduce: bool,
+}
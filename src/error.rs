@@ -0,0 +1,146 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+};
+
+use nemo::rule_model::components::tag::Tag;
+
+use crate::transformations::annotated_dependency_graphs::DependencyCycle;
+
+/// Errors of the metamorphic testing harness.
+/// They end the current transformation sequence, but not a campaign.
+#[derive(Debug)]
+pub enum MetamorphicError {
+    /// The rule file could not be read
+    ReadFile(PathBuf),
+    /// The rule file could not be parsed
+    Parse(PathBuf),
+    /// A folder or file could not be written
+    Write(PathBuf),
+    /// The data of an import could not be read
+    ReadData(PathBuf),
+    /// Nemo rejected the program produced by a transformation
+    InvalidTransformation,
+    /// The program derives no predicate that could be exported
+    NoDerivedPredicates,
+    /// The ADG has no output predicate yet
+    NoOutputPredicate,
+    /// No transformation can be applied
+    NoApplicableTransformation,
+    /// The ADG has no relational node for the relation
+    UnknownRelation(Tag),
+    /// The ADG has no node with this index
+    UnknownNode(usize),
+    /// Expected a relational node, but found a fact node
+    ExpectedRelationalNode(usize),
+    /// Expected a fact node, but found a relational node
+    ExpectedFactNode(usize),
+    /// The ADG has no loaded data for the imported resource
+    UnknownImport(String),
+    /// A relation depends negatively on itself, through the cycle
+    NegativeCycle(DependencyCycle),
+    /// Nemo failed to evaluate the input program
+    Evaluation { program: PathBuf, message: String },
+    /// Nemo failed to evaluate the transformed program, but not the input program
+    EngineFailure { program: PathBuf, message: String },
+    /// Nemo panicked while evaluating a program
+    EnginePanic { program: PathBuf, message: String },
+    /// A trace could not be read, written or replayed
+    Trace(String),
+    /// An ADG could not be converted to or from JSON
+    AdgJson(String),
+    /// The ADG no longer matches the program after a transformation.
+    /// A bug of the harness, not of the engine.
+    AdgDrift {
+        step: u32,
+        transformation: String,
+        problems: Vec<String>,
+    },
+    /// A transformation failed to update the ADG.
+    /// A bug of the harness, not of the engine.
+    FailedUpdate {
+        step: u32,
+        transformation: String,
+        problems: Vec<String>,
+    },
+    /// A failing sequence could not be reduced
+    Reduction(String),
+}
+impl Display for MetamorphicError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadFile(path) => write!(f, "Could not find rule file {}", path.display()),
+            Self::Parse(path) => write!(f, "Could not parse rule file {}", path.display()),
+            Self::Write(path) => write!(f, "Failed to write {}", path.display()),
+            Self::ReadData(path) => write!(f, "Could not read imported data {}", path.display()),
+            Self::InvalidTransformation => write!(f, "Failed to merge validation report"),
+            Self::NoDerivedPredicates => write!(f, "No predicates derived"),
+            Self::NoOutputPredicate => write!(f, "No output predicate set!"),
+            Self::NoApplicableTransformation => {
+                write!(f, "No transformation can be applied")
+            }
+            Self::UnknownRelation(tag) => write!(f, "Could not find node {tag}"),
+            Self::UnknownNode(index) => write!(f, "Could not find node with index {index}"),
+            Self::ExpectedRelationalNode(index) => write!(
+                f,
+                "Expected relation node for node {index} but found fact node"
+            ),
+            Self::ExpectedFactNode(index) => write!(
+                f,
+                "Expected fact node for node {index} but found relation node"
+            ),
+            Self::UnknownImport(resource) => write!(f, "No data loaded for import of {resource}"),
+            Self::NegativeCycle(cycle) => write!(
+                f,
+                "The program is not stratifiable, this cycle goes through a negative edge: {cycle}"
+            ),
+            Self::Evaluation { program, message } => write!(
+                f,
+                "Nemo failed to evaluate {}: {message}",
+                program.display()
+            ),
+            Self::EngineFailure { program, message } => write!(
+                f,
+                "Nemo failed to evaluate the transformed program {}: {message}",
+                program.display()
+            ),
+            Self::EnginePanic { program, message } => write!(
+                f,
+                "Nemo panicked while evaluating {}: {message}",
+                program.display()
+            ),
+            Self::Trace(message) => write!(f, "{message}"),
+            Self::AdgJson(message) => write!(f, "{message}"),
+            Self::AdgDrift {
+                step,
+                transformation,
+                problems,
+            } => {
+                write!(
+                    f,
+                    "The ADG does not match the program after step {step} ({transformation}):"
+                )?;
+                for problem in problems {
+                    write!(f, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+            Self::FailedUpdate {
+                step,
+                transformation,
+                problems,
+            } => {
+                write!(
+                    f,
+                    "Failed to update the ADG in step {step} ({transformation}):"
+                )?;
+                for problem in problems {
+                    write!(f, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+            Self::Reduction(message) => write!(f, "{message}"),
+        }
+    }
+}
+impl std::error::Error for MetamorphicError {}
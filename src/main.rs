@@ -1,33 +1,28 @@
-use std::{
-    fs::{File, create_dir, create_dir_all},
-    io::Write,
-    path::PathBuf,
-    process::exit,
-};
+use std::process::exit;
 
-use nemo::{
-    error::report::ProgramReport,
-    rule_file::RuleFile,
-    rule_model::{error::ValidationReport, programs::handle::ProgramHandle},
-};
+use clap::Parser;
 
+mod campaign;
+mod cli;
+mod error;
+mod oracle;
+mod reduce;
+mod trace;
+mod transformation_sequence;
 mod transformations;
 
-use transformations::{
-    MetamorphicTransformation, annotated_dependency_graphs::AnnotatedDependencyGraph,
-    name_rules::TransformationNameRules,
-    select_random_output_predicate::TransformationSelectRandomOutputPredicate,
+use campaign::{CampaignConfig, CampaignOutcome, run_campaign};
+use cli::{CampaignArgs, CheckArgs, Cli, Command, ReplayArgs, TransformArgs};
+use trace::TransformationTrace;
+use transformation_sequence::{
+    TransformationSequenceConfig, check_transformation_sequence_folder,
+    replay_transformation_sequence, run_transformation_sequence,
 };
+
 /*
 use lazy_static::lazy_static;
 use std::sync::Mutex;
  */
-use rand::SeedableRng;
-
-use crate::transformations::{
-    transformation_manager::{IterateMetamorphicTransformations, SomeMetamorphicTransformation},
-    transformation_types::TransformationTypes,
-};
 
 /*
 lazy_static! {
@@ -35,212 +30,140 @@ lazy_static! {
 }
  */
 fn main() {
-    const NUM_TRANSFORMATIONS: i32 = 32;
-    let seed: u64 = 42;
-    let transformation_types: TransformationTypes = TransformationTypes::CON;
-    println!("Using seed: {}", seed);
-    let name_of_transformation_sequence: &str = "Transformation Sequence 1";
-    let mut rng: rand_chacha::ChaCha8Rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
-
-    let vec_path: Vec<&str> = vec![
-        "/home/leo_repp/masterthesis/nemo/nemo-metamorphic-testing/examples/thesis-learning-examples/checkC.rls",
-        "/home/leo_repp/masterthesis/nemo/nemo-metamorphic-testing/examples/thesis-learning-examples/ancestry.rls",
-        "/home/leo_repp/masterthesis/nemo/nemo-metamorphic-testing/examples/wind-turbines/permissions.rls",
-    ];
-
-    // Open file
-    let path = PathBuf::from(vec_path[0]);
-    // "/home/leo_repp/masterthesis/nemo/nemo-metamorphic-testing/examples/wind-turbines/permissions.rls"
-    let file: RuleFile = match RuleFile::load(path) {
-        Err(_) => panic!("Could not find example file"),
-        Ok(file) => file,
-    };
-    // The program handle
-    let handle = ProgramHandle::from_file(&file);
-    let report = ProgramReport::new(file);
-
-    // Report building: Parser
-    match report.merge_program_parser_report(handle) {
-        Ok((program, report)) => {
-            // All these vars need to be made mutable in order to manipulate them
-            // over the different repetitions
-            let mut program: ProgramHandle = program;
-            let mut report: ProgramReport = report;
-
-            // Name all of the rules!
-            let transformation_name_rules: TransformationNameRules = TransformationNameRules::new();
-            let output_name_rules: Result<ProgramHandle, ValidationReport> =
-                program.transform(transformation_name_rules);
-            // Store validation report
-            let temp: Result<(ProgramHandle, ProgramReport), ProgramReport> =
-                report.merge_validation_report(&program, output_name_rules);
-            (program, report) = match temp {
-                Ok((p, r)) => (p, r),
-                Err(_) => {
-                    println!("Failed to merge validation report");
-                    exit(1);
-                }
-            };
-
-            // Construct the ADG
-            let mut adg: AnnotatedDependencyGraph =
-                match AnnotatedDependencyGraph::from_program(&program) {
-                    Some(adg) => adg,
-                    None => {
-                        println!("Failed to build adg");
-                        exit(1);
-                    }
-                };
-
-            // Choose output predicate. The transformation also sets the adg's output predicate
-            let transformation_output_chose: TransformationSelectRandomOutputPredicate =
-                TransformationSelectRandomOutputPredicate::new(&mut adg, &mut rng);
-            let output_choose_result: Result<ProgramHandle, ValidationReport> =
-                program.transform(transformation_output_chose);
-            // Store validation report
-            let temp: Result<(ProgramHandle, ProgramReport), ProgramReport> =
-                report.merge_validation_report(&program, output_choose_result);
-            (program, report) = match temp {
-                Ok((p, r)) => (p, r),
-                Err(_) => {
-                    println!("Failed to merge validation report");
-                    exit(1);
-                }
-            };
-
-            // Let the ADG calculate its stratum and ancestry
-            adg.calculate_ancestry_and_inverse_stratum();
-
-            // Create input folder
-            let input_folder_name = String::from("./") + name_of_transformation_sequence + "/input";
-            match create_dir_all(input_folder_name.clone()) {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("Failed to create input folder");
-                    exit(1);
-                }
-            }
-            // Write ADG to file
-            adg.write_self_to_file(
-                Some(input_folder_name.clone()),
-                Some(String::from("input_adg")),
-            );
-            // Done, write to file
-            match write_program_handle_to_file(
-                &program,
-                (input_folder_name.clone() + "/input_program").as_str(),
-            ) {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("Failed to write to file");
-                    exit(1);
-                }
-            }
-
-            // The available transformations
-            /* let mut transformation_manager =
-                           TransformationManager::new(&mut adg, &mut rng, transformation_types);
-            */
-            // Perform NUM_TRANSFORMATIONS transformations
-            for repetition in 1..=NUM_TRANSFORMATIONS {
-                println!("Starting transformation number {repetition}");
-                let trans_types: TransformationTypes = transformation_types.clone();
-                let mut transformation = SomeMetamorphicTransformation::Default();
-                let mut iter =
-                    IterateMetamorphicTransformations::new(&mut adg, &mut rng, trans_types);
-                loop {
-                    match iter.next() {
-                        None => continue,
-                        Some(loop_variable) => {
-                            transformation = loop_variable;
-                            break;
-                        }
-                    };
-                }
-
-                // calculate ith transformation
-                let current_result: Result<ProgramHandle, ValidationReport> =
-                    program.transform(transformation);
-
-                // transformations should instead work on a reference
-                // to the adg and then transform that
-                /* // Store ADG for next iteration
-                adg = transformation.fetch_adg(); */
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Transform(args) => transform(args),
+        Command::Check(args) => check(args),
+        Command::Campaign(args) => campaign(args),
+        Command::Replay(args) => replay(args),
+    }
+}
 
-                // Store validation report
-                let temp: Result<(ProgramHandle, ProgramReport), ProgramReport> =
-                    report.merge_validation_report(&program, current_result);
-                (program, report) = match temp {
-                    Ok((p, r)) => (p, r),
-                    Err(_) => {
-                        println!("Failed to merge validation report");
-                        exit(1);
-                    }
-                }
+/// Run one transformation sequence per input file.
+/// Exits with code 2 if the metamorphic relation is violated for any of them,
+/// otherwise with code 1 if any of them could not be run.
+fn transform(args: TransformArgs) {
+    let mut all_passed = true;
+    let mut any_error = false;
+    let several_inputs = args.input.len() > 1;
+    for input_file in args.input {
+        // With several input files, every file gets its own subfolder
+        let output_folder = if several_inputs {
+            let file_stem = input_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or(String::from("program"));
+            args.output.join(file_stem)
+        } else {
+            args.output.clone()
+        };
+        let config = TransformationSequenceConfig {
+            input_file,
+            seed: args.seed,
+            length: args.length,
+            transformation_type: args.transformation_type.clone(),
+            weights: args.weights.clone(),
+            output_folder,
+            reduce: args.reduce,
+            validate: args.validate,
+            load_imports: args.load_imports,
+        };
+        match run_transformation_sequence(&config) {
+            Ok(oracle_report) => {
+                println!("{oracle_report}");
+                all_passed &= oracle_report.passed();
             }
-
-            // Done, write to file
-
-            // Create output folder
-            let output_folder_name =
-                String::from("./") + name_of_transformation_sequence + "/output";
-            match create_dir_all(output_folder_name.clone()) {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("Failed to create output folder");
-                    exit(1);
-                }
+            Err(error) => {
+                // Skip this input file, but keep going with the others
+                println!("{}: {error}", config.input_file.display());
+                any_error = true;
             }
+        }
+    }
+    if !all_passed {
+        exit(2);
+    }
+    if any_error {
+        exit(1);
+    }
+}
 
-            // Write ADG to file
-            adg.write_self_to_file(
-                Some(output_folder_name.clone()),
-                Some(String::from("output_adg")),
-            );
-
-            // Write transformed program to file
-            match write_program_handle_to_file(
-                &program,
-                (output_folder_name.clone() + "/output_program").as_str(),
-            ) {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("Failed to write to file");
-                    exit(1);
-                }
+/// Check the metamorphic relation of an existing transformation sequence.
+/// Exits with code 2 if it is violated.
+fn check(args: CheckArgs) {
+    match check_transformation_sequence_folder(
+        &args.folder,
+        &args.import_folder,
+        args.transformation_type,
+    ) {
+        Ok(oracle_report) => {
+            println!("{oracle_report}");
+            if !oracle_report.passed() {
+                exit(2);
             }
         }
-
-        // Parsing failed!
-        Err(report) => error_handling(report),
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
     }
 }
 
-/// Error Handling
-pub fn error_handling(report: ProgramReport) {
-    let _ = report.eprint(false);
-    if report.contains_errors() {
-        std::process::exit(1);
+/// Run a campaign. Exits with code 2 if any run failed or crashed.
+fn campaign(args: CampaignArgs) {
+    let config = CampaignConfig {
+        corpus: args.corpus,
+        campaign_seed: args.seed,
+        seeds_per_program: args.seeds,
+        length: args.length,
+        transformation_types: args.transformation_types,
+        weights: args.weights,
+        output_folder: args.output,
+        reduce: args.reduce,
+        validate: args.validate,
+        load_imports: args.load_imports,
+    };
+    match run_campaign(&config) {
+        Ok(runs) => {
+            if runs.iter().any(|run| {
+                matches!(
+                    run.outcome,
+                    CampaignOutcome::Fail | CampaignOutcome::Crash(_)
+                )
+            }) {
+                exit(2);
+            }
+        }
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
     }
 }
 
-/// Write program to a file
-fn write_program_handle_to_file(program: &ProgramHandle, new_name: &str) -> std::io::Result<()> {
-    // Materialize the program
-    let program = program.materialize();
-    let to_str = program.to_string();
-
-    // Create a file to write to
-    let path = PathBuf::from(std::format!("./{new_name}.rls"));
-    let mut buffer: File = File::create(path)?;
-
-    // Write bytes
-    let mut pos = 0;
-    let data = to_str.as_bytes();
-    while pos < data.len() {
-        let bytes_written = buffer.write(&data[pos..])?;
-        pos += bytes_written;
+/// Rebuild a transformation sequence from its trace.
+/// Exits with code 2 if the metamorphic relation is violated.
+fn replay(args: ReplayArgs) {
+    let mut trace: TransformationTrace = match TransformationTrace::load(&args.trace) {
+        Ok(trace) => trace,
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
+    };
+    if let Some(input_file) = args.input {
+        trace.input_file = input_file;
+    }
+    match replay_transformation_sequence(&trace, args.output, args.reduce) {
+        Ok(oracle_report) => {
+            println!("{oracle_report}");
+            if !oracle_report.passed() {
+                exit(2);
+            }
+        }
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
     }
-
-    Ok(())
 }
@@ -0,0 +1,275 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use nemo::{api, datavalues::AnyDataValue, rule_model::components::tag::Tag};
+
+use crate::{
+    campaign::catch_crash, error::MetamorphicError,
+    transformations::transformation_types::TransformationTypes,
+};
+
+/// One row of the output predicate
+pub type Row = Vec<AnyDataValue>;
+
+/// The outcome of comparing the output predicate of the
+/// input program with that of the transformed program.
+pub struct OracleReport {
+    pub predicate: Tag,
+    pub transformation_type: TransformationTypes,
+    pub input_row_count: usize,
+    pub output_row_count: usize,
+    /// Rows derived by the input program, but not by the transformed program
+    pub missing: Vec<Row>,
+    /// Rows derived by the transformed program, but not by the input program
+    pub unexpected: Vec<Row>,
+}
+impl OracleReport {
+    /// Compare the rows of both programs under the intended transformation type.
+    /// EQU expects equal results, EXP a superset and CON a subset.
+    pub fn new(
+        predicate: Tag,
+        transformation_type: TransformationTypes,
+        input_rows: HashSet<Row>,
+        output_rows: HashSet<Row>,
+    ) -> Self {
+        let mut missing: Vec<Row> = match transformation_type {
+            TransformationTypes::EQU | TransformationTypes::EXP => {
+                input_rows.difference(&output_rows).cloned().collect()
+            }
+            TransformationTypes::CON => Vec::new(),
+        };
+        let mut unexpected: Vec<Row> = match transformation_type {
+            TransformationTypes::EQU | TransformationTypes::CON => {
+                output_rows.difference(&input_rows).cloned().collect()
+            }
+            TransformationTypes::EXP => Vec::new(),
+        };
+        // Make the report deterministic
+        missing.sort_by_key(|row| format_row(&predicate, row));
+        unexpected.sort_by_key(|row| format_row(&predicate, row));
+        Self {
+            predicate,
+            transformation_type,
+            input_row_count: input_rows.len(),
+            output_row_count: output_rows.len(),
+            missing,
+            unexpected,
+        }
+    }
+
+    /// Whether the metamorphic relation holds
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+
+    /// Write the report to `verdict.txt` in the given folder
+    pub fn write_to_folder(&self, folder: &Path) -> std::io::Result<()> {
+        std::fs::write(folder.join("verdict.txt"), self.to_string())
+    }
+}
+impl Display for OracleReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Verdict: {}",
+            if self.passed() { "PASS" } else { "FAIL" }
+        )?;
+        writeln!(f, "Transformation type: {}", self.transformation_type)?;
+        writeln!(f, "Output predicate: {}", self.predicate)?;
+        writeln!(f, "Rows of input program: {}", self.input_row_count)?;
+        writeln!(f, "Rows of output program: {}", self.output_row_count)?;
+        if !self.missing.is_empty() {
+            writeln!(
+                f,
+                "Derived by the input program, but not by the output program ({}):",
+                self.missing.len()
+            )?;
+            for row in &self.missing {
+                writeln!(f, "    {}", format_row(&self.predicate, row))?;
+            }
+        }
+        if !self.unexpected.is_empty() {
+            writeln!(
+                f,
+                "Derived by the output program, but not by the input program ({}):",
+                self.unexpected.len()
+            )?;
+            for row in &self.unexpected {
+                writeln!(f, "    {}", format_row(&self.predicate, row))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format a row as a fact of the predicate
+fn format_row(predicate: &Tag, row: &Row) -> String {
+    let terms: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+    format!("{}({})", predicate, terms.join(", "))
+}
+
+/// Reason over both programs and compare their results for `predicate`.
+/// Relative imports are resolved against `import_folder`.
+pub fn check_transformation_sequence(
+    input_program: &Path,
+    output_program: &Path,
+    import_folder: &Path,
+    predicate: &Tag,
+    transformation_type: TransformationTypes,
+) -> Result<OracleReport, MetamorphicError> {
+    println!("Evaluating input program {}", input_program.display());
+    let input_rows = evaluate_program(input_program, import_folder, predicate)?;
+    println!("Evaluating output program {}", output_program.display());
+    // Nemo handles the input program, so failing on the output program is a finding
+    let output_rows = match evaluate_program(output_program, import_folder, predicate) {
+        Err(MetamorphicError::Evaluation { program, message }) => {
+            return Err(MetamorphicError::EngineFailure { program, message });
+        }
+        result => result?,
+    };
+    Ok(OracleReport::new(
+        predicate.clone(),
+        transformation_type,
+        input_rows,
+        output_rows,
+    ))
+}
+
+/// Run nemo on the rule file and collect all rows of `predicate`
+pub fn evaluate_program(
+    program: &Path,
+    import_folder: &Path,
+    predicate: &Tag,
+) -> Result<HashSet<Row>, MetamorphicError> {
+    let program: PathBuf = match program.canonicalize() {
+        Ok(path) => path,
+        Err(_) => return Err(MetamorphicError::ReadFile(program.to_path_buf())),
+    };
+    // Nemo resolves relative import paths against the working directory
+    let previous_folder = std::env::current_dir().ok();
+    if !import_folder.as_os_str().is_empty() && std::env::set_current_dir(import_folder).is_err() {
+        return Err(MetamorphicError::Evaluation {
+            program,
+            message: format!(
+                "Could not change into import folder {}",
+                import_folder.display()
+            ),
+        });
+    }
+    // A panic here is a crash of nemo, not of the harness
+    let result = match catch_crash(|| evaluate_program_in_current_folder(&program, predicate)) {
+        Ok(result) => result,
+        Err(message) => Err(MetamorphicError::EnginePanic {
+            program: program.clone(),
+            message,
+        }),
+    };
+    if let Some(previous_folder) = previous_folder {
+        let _ = std::env::set_current_dir(previous_folder);
+    }
+    result
+}
+
+fn evaluate_program_in_current_folder(
+    program: &Path,
+    predicate: &Tag,
+) -> Result<HashSet<Row>, MetamorphicError> {
+    let evaluation_error = |message: String| MetamorphicError::Evaluation {
+        program: program.to_path_buf(),
+        message,
+    };
+    let mut engine = match api::load(program.to_path_buf()) {
+        Ok(engine) => engine,
+        Err(error) => return Err(evaluation_error(format!("loading failed: {error}"))),
+    };
+    if let Err(error) = api::reason(&mut engine) {
+        return Err(evaluation_error(format!("reasoning failed: {error}")));
+    }
+    match engine.predicate_rows(predicate) {
+        Ok(Some(rows)) => Ok(rows.collect()),
+        // The predicate is empty
+        Ok(None) => Ok(HashSet::new()),
+        Err(error) => Err(evaluation_error(format!(
+            "could not read predicate {predicate}: {error}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use nemo::{datavalues::AnyDataValue, rule_model::components::tag::Tag};
+
+    use super::{OracleReport, Row};
+    use crate::transformations::transformation_types::TransformationTypes;
+
+    fn row(value: i64) -> Row {
+        vec![AnyDataValue::new_integer_from_i64(value)]
+    }
+
+    fn rows(values: &[i64]) -> HashSet<Row> {
+        values.iter().map(|value| row(*value)).collect()
+    }
+
+    fn report(
+        transformation_type: TransformationTypes,
+        input: &[i64],
+        output: &[i64],
+    ) -> OracleReport {
+        OracleReport::new(
+            Tag::new(String::from("out")),
+            transformation_type,
+            rows(input),
+            rows(output),
+        )
+    }
+
+    #[test]
+    fn equ_passes_on_equal_rows() {
+        assert!(report(TransformationTypes::EQU, &[1, 2], &[2, 1]).passed());
+    }
+
+    #[test]
+    fn equ_reports_missing_and_unexpected_rows() {
+        let report = report(TransformationTypes::EQU, &[1, 2], &[2, 3]);
+        assert!(!report.passed());
+        assert_eq!(report.missing, vec![row(1)]);
+        assert_eq!(report.unexpected, vec![row(3)]);
+        assert_eq!(report.input_row_count, 2);
+        assert_eq!(report.output_row_count, 2);
+    }
+
+    #[test]
+    fn exp_passes_on_a_superset() {
+        let report = report(TransformationTypes::EXP, &[1], &[1, 2]);
+        assert!(report.passed());
+        assert!(report.unexpected.is_empty());
+    }
+
+    #[test]
+    fn exp_fails_on_a_missing_row() {
+        let report = report(TransformationTypes::EXP, &[1, 2], &[1, 3]);
+        assert!(!report.passed());
+        assert_eq!(report.missing.len(), 1);
+        assert!(report.unexpected.is_empty());
+    }
+
+    #[test]
+    fn con_passes_on_a_subset() {
+        let report = report(TransformationTypes::CON, &[1, 2], &[2]);
+        assert!(report.passed());
+        assert!(report.missing.is_empty());
+    }
+
+    #[test]
+    fn con_fails_on_an_extra_row() {
+        let report = report(TransformationTypes::CON, &[1, 2], &[2, 3]);
+        assert!(!report.passed());
+        assert!(report.missing.is_empty());
+        assert_eq!(report.unexpected.len(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::create_dir_all,
+    path::Path,
+};
+
+use nemo::rule_model::{components::tag::Tag, programs::handle::ProgramHandle};
+use rand::SeedableRng;
+
+use crate::{
+    error::MetamorphicError,
+    oracle::{OracleReport, Row, evaluate_program},
+    transformation_sequence::write_program_and_imports,
+    transformations::{
+        annotated_dependency_graphs::AnnotatedDependencyGraph,
+        transformation_step::TransformationStep, transformation_types::TransformationTypes,
+    },
+};
+
+/// A failing transformation sequence that should be reduced
+pub struct FailingSequence<'a> {
+    /// The program before the first step
+    pub program: &'a ProgramHandle,
+    /// The ADG before the first step, with ancestry and inverse stratum
+    pub adg: &'a AnnotatedDependencyGraph,
+    /// The steps of the failing sequence
+    pub steps: &'a [TransformationStep],
+    /// The written input program, i.e. `program`
+    pub input_program: &'a Path,
+    pub import_folder: &'a Path,
+    pub output_predicate: &'a Tag,
+    pub transformation_type: TransformationTypes,
+}
+
+/// Find a minimal subsequence of the steps that still violates the
+/// metamorphic relation (ddmin) and write it to `folder`.
+/// Returns the indices of the remaining steps.
+pub fn reduce_failing_sequence(
+    sequence: &FailingSequence,
+    folder: &Path,
+) -> Result<Vec<usize>, MetamorphicError> {
+    if create_dir_all(folder).is_err() {
+        return Err(MetamorphicError::Write(folder.to_path_buf()));
+    }
+    println!(
+        "Reducing failing sequence of {} steps",
+        sequence.steps.len()
+    );
+    let input_rows: HashSet<Row> = evaluate_program(
+        sequence.input_program,
+        sequence.import_folder,
+        sequence.output_predicate,
+    )?;
+    let candidate_path = folder.join("candidate_program.rls");
+    let mut tested: HashMap<Vec<usize>, bool> = HashMap::new();
+    let mut fails = |subsequence: &[usize]| -> bool {
+        if let Some(result) = tested.get(subsequence) {
+            return *result;
+        }
+        let result = match replay_steps(sequence, subsequence) {
+            // The subsequence can't be replayed, so it does not reproduce the failure
+            None => false,
+            Some((program, adg)) => {
+                write_program_and_imports(&program, &adg, &candidate_path).is_ok()
+                    && match evaluate_program(
+                        &candidate_path,
+                        sequence.import_folder,
+                        sequence.output_predicate,
+                    ) {
+                        Ok(output_rows) => !OracleReport::new(
+                            sequence.output_predicate.clone(),
+                            sequence.transformation_type.clone(),
+                            input_rows.clone(),
+                            output_rows,
+                        )
+                        .passed(),
+                        // A different failure
+                        Err(_) => false,
+                    }
+            }
+        };
+        tested.insert(subsequence.to_vec(), result);
+        result
+    };
+
+    let all_steps: Vec<usize> = (0..sequence.steps.len()).collect();
+    if !fails(&all_steps) {
+        let _ = std::fs::remove_file(&candidate_path);
+        return Err(MetamorphicError::Reduction(String::from(
+            "Replaying the recorded steps does not reproduce the failure",
+        )));
+    }
+    let minimal: Vec<usize> = ddmin(all_steps, &mut fails);
+    let _ = std::fs::remove_file(&candidate_path);
+    println!(
+        "Reduced failing sequence to {} of {} steps after {} tests",
+        minimal.len(),
+        sequence.steps.len(),
+        tested.len()
+    );
+
+    write_reduced_sequence(sequence, &minimal, folder)?;
+    Ok(minimal)
+}
+
+/// Replay the steps with the given indices on copies of the original program and ADG.
+/// Returns None if one of the steps can't be applied or fails.
+pub fn replay_steps(
+    sequence: &FailingSequence,
+    subsequence: &[usize],
+) -> Option<(ProgramHandle, AnnotatedDependencyGraph)> {
+    let mut program: ProgramHandle = sequence.program.clone();
+    let mut adg: AnnotatedDependencyGraph = sequence.adg.clone();
+    // Replaying does not draw random numbers
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+    for index in subsequence {
+        let transformation = sequence.steps[*index]
+            .replay(&mut adg, &mut rng, sequence.transformation_type.clone())
+            .ok()??;
+        program = program.transform(transformation).ok()?;
+        if !adg.take_failed_updates().is_empty() {
+            return None;
+        }
+    }
+    Some((program, adg))
+}
+
+/// Delta debugging (Zeller & Hildebrandt): reduce `steps` to a
+/// 1-minimal subsequence for which `fails` still holds.
+fn ddmin(mut steps: Vec<usize>, fails: &mut impl FnMut(&[usize]) -> bool) -> Vec<usize> {
+    let mut granularity: usize = 2;
+    while steps.len() >= 2 {
+        let chunk_size = steps.len().div_ceil(granularity);
+        let chunks: Vec<Vec<usize>> = steps.chunks(chunk_size).map(|c| c.to_vec()).collect();
+
+        // Reduce to a chunk
+        if let Some(chunk) = chunks.iter().find(|chunk| fails(chunk)) {
+            steps = chunk.clone();
+            granularity = 2;
+            continue;
+        }
+        // Reduce to a complement
+        let complement_found = (0..chunks.len()).find_map(|skip| {
+            let complement: Vec<usize> = chunks
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != skip)
+                .flat_map(|(_, chunk)| chunk.iter().copied())
+                .collect();
+            if fails(&complement) {
+                Some(complement)
+            } else {
+                None
+            }
+        });
+        if let Some(complement) = complement_found {
+            steps = complement;
+            granularity = std::cmp::max(granularity - 1, 2);
+            continue;
+        }
+        // Increase granularity
+        if granularity >= steps.len() {
+            break;
+        }
+        granularity = std::cmp::min(granularity * 2, steps.len());
+    }
+    steps
+}
+
+/// Write the reduced output program, its ADG, the remaining steps and the verdict
+fn write_reduced_sequence(
+    sequence: &FailingSequence,
+    minimal: &[usize],
+    folder: &Path,
+) -> Result<(), MetamorphicError> {
+    let (program, adg) = match replay_steps(sequence, minimal) {
+        Some(replayed) => replayed,
+        None => {
+            return Err(MetamorphicError::Reduction(String::from(
+                "Failed to replay reduced sequence",
+            )));
+        }
+    };
+    let program_path = folder.join("output_program.rls");
+    write_program_and_imports(&program, &adg, &program_path)?;
+    adg.write_self_to_file(
+        Some(folder.to_string_lossy().into_owned()),
+        Some(String::from("output_adg")),
+    )?;
+    adg.write_json_to_file(&folder.join("output_adg.json"))?;
+
+    let mut steps_str = String::new();
+    for index in minimal {
+        steps_str += &format!("{}: {:?}\n", index + 1, sequence.steps[*index]);
+    }
+    if std::fs::write(folder.join("steps.txt"), steps_str).is_err() {
+        return Err(MetamorphicError::Write(folder.join("steps.txt")));
+    }
+
+    let oracle_report: OracleReport = crate::oracle::check_transformation_sequence(
+        sequence.input_program,
+        &program_path,
+        sequence.import_folder,
+        sequence.output_predicate,
+        sequence.transformation_type.clone(),
+    )?;
+    if oracle_report.write_to_folder(folder).is_err() {
+        return Err(MetamorphicError::Write(folder.join("verdict.txt")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ddmin;
+
+    #[test]
+    fn ddmin_finds_single_culprit() {
+        let steps: Vec<usize> = (0..10).collect();
+        let minimal = ddmin(steps, &mut |subsequence: &[usize]| subsequence.contains(&7));
+        assert_eq!(minimal, vec![7]);
+    }
+
+    #[test]
+    fn ddmin_finds_interacting_culprits() {
+        let steps: Vec<usize> = (0..10).collect();
+        let minimal = ddmin(steps, &mut |subsequence: &[usize]| {
+            subsequence.contains(&2) && subsequence.contains(&6)
+        });
+        assert_eq!(minimal, vec![2, 6]);
+    }
+
+    #[test]
+    fn ddmin_keeps_input_if_every_step_is_needed() {
+        let steps: Vec<usize> = (0..6).collect();
+        let minimal = ddmin(steps.clone(), &mut |subsequence: &[usize]| {
+            subsequence.len() == 6
+        });
+        assert_eq!(minimal, steps);
+    }
+
+    #[test]
+    fn ddmin_result_is_one_minimal() {
+        let steps: Vec<usize> = (0..16).collect();
+        let mut fails =
+            |subsequence: &[usize]| [1, 9, 14].iter().all(|step| subsequence.contains(step));
+        let minimal = ddmin(steps, &mut fails);
+        assert_eq!(minimal, vec![1, 9, 14]);
+        for skip in 0..minimal.len() {
+            let mut smaller = minimal.clone();
+            smaller.remove(skip);
+            assert!(!fails(&smaller));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use nemo::rule_model::components::tag::Tag;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::MetamorphicError,
+    transformations::{
+        serialization, transformation_step::TransformationStep,
+        transformation_types::TransformationTypes,
+    },
+};
+
+/// Machine readable log of a transformation sequence.
+/// Together with the input file it determines the output program,
+/// independent of how many random numbers the transformations draw.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TransformationTrace {
+    /// The rule file the sequence started from
+    pub input_file: PathBuf,
+    /// Seed of the random number generator
+    pub seed: u64,
+    pub transformation_type: TransformationTypes,
+    /// The output predicate chosen for the sequence
+    #[serde(with = "serialization::tag")]
+    pub output_predicate: Tag,
+    pub steps: Vec<TraceEntry>,
+}
+
+/// One applied transformation of a trace
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Position of the transformation in the original sequence, starting at 1
+    pub step_number: u32,
+    /// Word position of the random number generator before the step.
+    /// Stored as a string, as it does not fit into a JSON number.
+    pub rng_word_pos: String,
+    pub step: TransformationStep,
+}
+
+impl TransformationTrace {
+    pub fn new(
+        input_file: PathBuf,
+        seed: u64,
+        transformation_type: TransformationTypes,
+        output_predicate: Tag,
+    ) -> Self {
+        Self {
+            input_file,
+            seed,
+            transformation_type,
+            output_predicate,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Append the step that was applied when the rng was at `rng_word_pos`
+    pub fn push(&mut self, rng_word_pos: u128, step: TransformationStep) {
+        self.steps.push(TraceEntry {
+            step_number: self.steps.len() as u32 + 1,
+            rng_word_pos: rng_word_pos.to_string(),
+            step,
+        });
+    }
+
+    /// The recorded steps, without their metadata
+    pub fn transformation_steps(&self) -> Vec<TransformationStep> {
+        self.steps.iter().map(|entry| entry.step.clone()).collect()
+    }
+
+    /// The trace of only the entries with the given indices
+    pub fn subsequence(&self, indices: &[usize]) -> Self {
+        Self {
+            steps: indices
+                .iter()
+                .map(|index| self.steps[*index].clone())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Write the trace as `trace.json` to the given folder
+    pub fn write_to_folder(&self, folder: &Path) -> Result<(), MetamorphicError> {
+        let json = match serde_json::to_string_pretty(self) {
+            Ok(json) => json,
+            Err(error) => {
+                return Err(MetamorphicError::Trace(format!(
+                    "Failed to serialize trace: {error}"
+                )));
+            }
+        };
+        let path = folder.join("trace.json");
+        match std::fs::write(&path, json) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(MetamorphicError::Write(path)),
+        }
+    }
+
+    /// Read a trace from a json file
+    pub fn load(path: &Path) -> Result<Self, MetamorphicError> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(_) => return Err(MetamorphicError::ReadFile(path.to_path_buf())),
+        };
+        match serde_json::from_str(&json) {
+            Ok(trace) => Ok(trace),
+            Err(error) => Err(MetamorphicError::Trace(format!(
+                "Invalid trace {}: {error}",
+                path.display()
+            ))),
+        }
+    }
+}
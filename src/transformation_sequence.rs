@@ -0,0 +1,506 @@
+use std::{
+    fs::{File, create_dir_all},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use nemo::{
+    error::report::ProgramReport,
+    rule_file::RuleFile,
+    rule_model::{
+        components::tag::Tag,
+        error::ValidationReport,
+        programs::{ProgramRead, handle::ProgramHandle},
+    },
+};
+use rand::SeedableRng;
+
+use crate::error::MetamorphicError;
+use crate::oracle::{OracleReport, check_transformation_sequence};
+use crate::reduce::{FailingSequence, reduce_failing_sequence};
+use crate::trace::TransformationTrace;
+use crate::transformations::{
+    annotated_dependency_graphs::AnnotatedDependencyGraph,
+    name_rules::TransformationNameRules,
+    select_random_output_predicate::TransformationSelectRandomOutputPredicate,
+    transformation_manager::{SomeMetamorphicTransformation, TransformationWeights},
+    transformation_step::TransformationStep,
+    transformation_types::TransformationTypes,
+};
+
+/// Everything needed to run one transformation sequence on one rule file
+pub struct TransformationSequenceConfig {
+    /// The rule file to transform
+    pub input_file: PathBuf,
+    /// Seed of the random number generator
+    pub seed: u64,
+    /// Number of transformations to apply
+    pub length: u32,
+    /// Intended metamorphic relation between input and output program
+    pub transformation_type: TransformationTypes,
+    /// Relative weights with which the applicable transformations are chosen
+    pub weights: TransformationWeights,
+    /// Folder that will contain the `input` and `output` folders
+    pub output_folder: PathBuf,
+    /// Reduce the sequence to a minimal failing one if the oracle fails
+    pub reduce: bool,
+    /// Cross-check the ADG against the program after every transformation
+    pub validate: bool,
+    /// Load the rows of imported csv and tsv files into the ADG, so they can be transformed
+    pub load_imports: bool,
+}
+
+/// Parse the rule file, transform it `length` times, write the
+/// input and output programs (and their ADGs) and the trace to the
+/// output folder and check the metamorphic relation between both programs.
+pub fn run_transformation_sequence(
+    config: &TransformationSequenceConfig,
+) -> Result<OracleReport, MetamorphicError> {
+    println!("Using seed: {}", config.seed);
+    let mut rng: rand_chacha::ChaCha8Rng = rand_chacha::ChaCha8Rng::seed_from_u64(config.seed);
+
+    let (mut program, mut report, mut adg, output_predicate) =
+        prepare_sequence(config, &mut rng, None)?;
+    if config.validate {
+        validate_adg(&adg, &program, 0, String::from("building the ADG"))?;
+    }
+    // Keep the state before the first step to replay parts of the sequence
+    let initial_state: Option<(ProgramHandle, AnnotatedDependencyGraph)> = if config.reduce {
+        Some((program.clone(), adg.clone()))
+    } else {
+        None
+    };
+    let mut trace = TransformationTrace::new(
+        config.input_file.clone(),
+        config.seed,
+        config.transformation_type.clone(),
+        output_predicate,
+    );
+
+    // Perform `length` transformations
+    for repetition in 1..=config.length {
+        println!("Starting transformation number {repetition}");
+        let rng_word_pos: u128 = rng.get_word_pos();
+        let num_applied_steps: usize = adg.get_applied_steps().len();
+        let trans_types: TransformationTypes = config.transformation_type.clone();
+        let transformation: SomeMetamorphicTransformation =
+            match SomeMetamorphicTransformation::choose(
+                &mut adg,
+                &mut rng,
+                trans_types,
+                &config.weights,
+            )? {
+                Some(transformation) => transformation,
+                // Nothing applies to the first step: there is nothing to test
+                None if trace.steps.is_empty() => {
+                    return Err(MetamorphicError::NoApplicableTransformation);
+                }
+                // Keep the steps applied so far and check them
+                None => {
+                    println!(
+                        "No transformation applies after {} of {} transformations, ending the sequence",
+                        repetition - 1,
+                        config.length
+                    );
+                    break;
+                }
+            };
+
+        // calculate ith transformation
+        let current_result: Result<ProgramHandle, ValidationReport> =
+            program.transform(transformation);
+
+        // Store validation report
+        (program, report) = merge_validation_report(report, &program, current_result)?;
+
+        // The transformation recorded the step it applied, if it changed anything
+        if let Some(step) = adg.get_applied_steps().get(num_applied_steps) {
+            trace.push(rng_word_pos, step.clone());
+        }
+        let transformation: String = match adg.get_applied_steps().get(num_applied_steps) {
+            Some(step) => format!("{step:?}"),
+            None => String::from("no change"),
+        };
+        check_failed_updates(&mut adg, repetition, transformation.clone())?;
+
+        if config.validate {
+            validate_adg(&adg, &program, repetition, transformation)?;
+        }
+    }
+
+    finish_sequence(config, &program, &adg, initial_state, &trace)
+}
+
+/// Rebuild the output program of a trace from its input file,
+/// without drawing random numbers, and check the metamorphic relation again.
+pub fn replay_transformation_sequence(
+    trace: &TransformationTrace,
+    output_folder: PathBuf,
+    reduce: bool,
+) -> Result<OracleReport, MetamorphicError> {
+    let config = TransformationSequenceConfig {
+        input_file: trace.input_file.clone(),
+        seed: trace.seed,
+        length: trace.steps.len() as u32,
+        transformation_type: trace.transformation_type.clone(),
+        // Replaying does not choose transformations
+        weights: TransformationWeights::default(),
+        output_folder,
+        reduce,
+        validate: false,
+        // The data has to be loaded if the trace changes it
+        load_imports: trace
+            .steps
+            .iter()
+            .any(|entry| entry.step.changes_imported_data()),
+    };
+    // Replaying does not draw random numbers
+    let mut rng: rand_chacha::ChaCha8Rng = rand_chacha::ChaCha8Rng::seed_from_u64(trace.seed);
+
+    let (mut program, mut report, mut adg, _) =
+        prepare_sequence(&config, &mut rng, Some(trace.output_predicate.clone()))?;
+    let initial_state: Option<(ProgramHandle, AnnotatedDependencyGraph)> = if config.reduce {
+        Some((program.clone(), adg.clone()))
+    } else {
+        None
+    };
+
+    for entry in &trace.steps {
+        println!("Replaying transformation number {}", entry.step_number);
+        let transformation: SomeMetamorphicTransformation =
+            match entry
+                .step
+                .replay(&mut adg, &mut rng, config.transformation_type.clone())?
+            {
+                Some(transformation) => transformation,
+                None => {
+                    return Err(MetamorphicError::Trace(format!(
+                        "Step {} of the trace can't be applied: {:?}",
+                        entry.step_number, entry.step
+                    )));
+                }
+            };
+        let current_result: Result<ProgramHandle, ValidationReport> =
+            program.transform(transformation);
+        (program, report) = merge_validation_report(report, &program, current_result)?;
+        check_failed_updates(&mut adg, entry.step_number, format!("{:?}", entry.step))?;
+    }
+
+    finish_sequence(&config, &program, &adg, initial_state, trace)
+}
+
+/// Parse the rule file, name its rules, choose the output predicate
+/// (randomly, if none is given), compute ancestry and inverse stratum
+/// and write the input program and ADG.
+fn prepare_sequence(
+    config: &TransformationSequenceConfig,
+    rng: &mut rand_chacha::ChaCha8Rng,
+    output_predicate: Option<Tag>,
+) -> Result<(ProgramHandle, ProgramReport, AnnotatedDependencyGraph, Tag), MetamorphicError> {
+    let (mut program, mut report): (ProgramHandle, ProgramReport) =
+        load_program(&config.input_file)?;
+
+    // Name all of the rules!
+    let transformation_name_rules: TransformationNameRules = TransformationNameRules::new();
+    let output_name_rules: Result<ProgramHandle, ValidationReport> =
+        program.transform(transformation_name_rules);
+    // Store validation report
+    (program, report) = merge_validation_report(report, &program, output_name_rules)?;
+
+    // Construct the ADG
+    let mut adg: AnnotatedDependencyGraph = AnnotatedDependencyGraph::from_program(&program)?;
+    if config.load_imports {
+        adg.load_imports(&import_folder_of(&config.input_file))?;
+    }
+
+    // Choose output predicate. The transformation also sets the adg's output predicate
+    let transformation_output_chose: TransformationSelectRandomOutputPredicate =
+        match output_predicate {
+            None => TransformationSelectRandomOutputPredicate::new(&mut adg, rng, &program)?,
+            Some(predicate) => {
+                TransformationSelectRandomOutputPredicate::with_predicate(&mut adg, predicate)
+            }
+        };
+    let output_choose_result: Result<ProgramHandle, ValidationReport> =
+        program.transform(transformation_output_chose);
+    // Store validation report
+    (program, report) = merge_validation_report(report, &program, output_choose_result)?;
+    let output_predicate: Tag = match adg.get_output_predicate() {
+        Some(tag) => tag.clone(),
+        None => return Err(MetamorphicError::NoOutputPredicate),
+    };
+
+    // Let the ADG calculate its stratum and ancestry
+    adg.calculate_ancestry_and_inverse_stratum()?;
+
+    // Create input folder, write ADG and program
+    let input_folder = config.output_folder.join("input");
+    write_adg_and_program(&adg, &program, &input_folder, "input")?;
+
+    Ok((program, report, adg, output_predicate))
+}
+
+/// Write the output program, its ADG and the trace, check the metamorphic
+/// relation and reduce the sequence if it fails and the config asks for it.
+fn finish_sequence(
+    config: &TransformationSequenceConfig,
+    program: &ProgramHandle,
+    adg: &AnnotatedDependencyGraph,
+    initial_state: Option<(ProgramHandle, AnnotatedDependencyGraph)>,
+    trace: &TransformationTrace,
+) -> Result<OracleReport, MetamorphicError> {
+    // The maintained ADG has to match the one built from the output program
+    let differences: Vec<String> = adg.diff_with_rebuilt(program)?;
+    if !differences.is_empty() {
+        return Err(MetamorphicError::AdgDrift {
+            step: trace.steps.len() as u32,
+            transformation: String::from("the end of the sequence"),
+            problems: differences,
+        });
+    }
+
+    // Done, create output folder, write ADG and transformed program
+    let input_folder = config.output_folder.join("input");
+    let output_folder = config.output_folder.join("output");
+    write_adg_and_program(adg, program, &output_folder, "output")?;
+    trace.write_to_folder(&config.output_folder)?;
+
+    // Evaluate both programs and check the metamorphic relation
+    let import_folder: PathBuf = import_folder_of(&config.input_file);
+    let oracle_report: OracleReport = check_transformation_sequence(
+        &input_folder.join("input_program.rls"),
+        &output_folder.join("output_program.rls"),
+        &import_folder,
+        &trace.output_predicate,
+        config.transformation_type.clone(),
+    )?;
+    if oracle_report
+        .write_to_folder(&config.output_folder)
+        .is_err()
+    {
+        return Err(MetamorphicError::Write(
+            config.output_folder.join("verdict.txt"),
+        ));
+    }
+
+    // Find a minimal failing subsequence
+    if let Some((initial_program, initial_adg)) = &initial_state {
+        if !oracle_report.passed() {
+            let steps: Vec<TransformationStep> = trace.transformation_steps();
+            let failing_sequence = FailingSequence {
+                program: initial_program,
+                adg: initial_adg,
+                steps: &steps,
+                input_program: &input_folder.join("input_program.rls"),
+                import_folder: &import_folder,
+                output_predicate: &trace.output_predicate,
+                transformation_type: config.transformation_type.clone(),
+            };
+            // The failure itself is found, a failed reduction is no reason to discard it
+            let reduced_folder = config.output_folder.join("reduced");
+            match reduce_failing_sequence(&failing_sequence, &reduced_folder) {
+                Ok(minimal) => trace
+                    .subsequence(&minimal)
+                    .write_to_folder(&reduced_folder)?,
+                Err(error) => println!("Failed to reduce sequence: {error}"),
+            }
+        }
+    }
+
+    Ok(oracle_report)
+}
+
+/// Cross-check the ADG against the program and the ADG rebuilt from it,
+/// failing with the step that made them drift apart
+fn validate_adg(
+    adg: &AnnotatedDependencyGraph,
+    program: &ProgramHandle,
+    step: u32,
+    transformation: String,
+) -> Result<(), MetamorphicError> {
+    let mut problems: Vec<String> = adg.validate(program);
+    problems.extend(adg.diff_with_rebuilt(program)?);
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(MetamorphicError::AdgDrift {
+        step,
+        transformation,
+        problems,
+    })
+}
+
+/// Fail with the errors the transformation of the step ran into while updating the ADG
+fn check_failed_updates(
+    adg: &mut AnnotatedDependencyGraph,
+    step: u32,
+    transformation: String,
+) -> Result<(), MetamorphicError> {
+    let problems: Vec<String> = adg.take_failed_updates();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    Err(MetamorphicError::FailedUpdate {
+        step,
+        transformation,
+        problems,
+    })
+}
+
+/// Load and parse a rule file
+pub fn load_program(path: &Path) -> Result<(ProgramHandle, ProgramReport), MetamorphicError> {
+    // Open file
+    let file: RuleFile = match RuleFile::load(path.to_path_buf()) {
+        Err(_) => return Err(MetamorphicError::ReadFile(path.to_path_buf())),
+        Ok(file) => file,
+    };
+    // The program handle
+    let handle = ProgramHandle::from_file(&file);
+    let report = ProgramReport::new(file);
+
+    // Report building: Parser
+    match report.merge_program_parser_report(handle) {
+        Ok((program, report)) => Ok((program, report)),
+        // Parsing failed!
+        Err(report) => {
+            let _ = report.eprint(false);
+            Err(MetamorphicError::Parse(path.to_path_buf()))
+        }
+    }
+}
+
+/// Check the metamorphic relation of an already written transformation sequence.
+/// The output predicate is the one exported by the input program.
+pub fn check_transformation_sequence_folder(
+    folder: &Path,
+    import_folder: &Path,
+    transformation_type: TransformationTypes,
+) -> Result<OracleReport, MetamorphicError> {
+    let input_program_path = folder.join("input").join("input_program.rls");
+    let (input_program, _) = load_program(&input_program_path)?;
+    let output_predicate: Tag = match input_program.exports().next() {
+        Some(export) => export.predicate().clone(),
+        None => return Err(MetamorphicError::NoOutputPredicate),
+    };
+    let oracle_report: OracleReport = check_transformation_sequence(
+        &input_program_path,
+        &folder.join("output").join("output_program.rls"),
+        import_folder,
+        &output_predicate,
+        transformation_type,
+    )?;
+    if oracle_report.write_to_folder(folder).is_err() {
+        return Err(MetamorphicError::Write(folder.join("verdict.txt")));
+    }
+    Ok(oracle_report)
+}
+
+/// Imports of a rule file are relative to the folder the rule file is in
+pub fn import_folder_of(rule_file: &Path) -> PathBuf {
+    rule_file
+        .parent()
+        .map(|parent| parent.to_path_buf())
+        .unwrap_or_default()
+}
+
+/// Merge the result of a transformation into the report,
+/// printing the report if the transformation failed.
+fn merge_validation_report(
+    report: ProgramReport,
+    program: &ProgramHandle,
+    result: Result<ProgramHandle, ValidationReport>,
+) -> Result<(ProgramHandle, ProgramReport), MetamorphicError> {
+    match report.merge_validation_report(program, result) {
+        Ok((p, r)) => Ok((p, r)),
+        Err(report) => {
+            let _ = report.eprint(false);
+            Err(MetamorphicError::InvalidTransformation)
+        }
+    }
+}
+
+/// Create `folder` and write `<prefix>_adg.dot`, `<prefix>_adg.json` and `<prefix>_program.rls` into it
+fn write_adg_and_program(
+    adg: &AnnotatedDependencyGraph,
+    program: &ProgramHandle,
+    folder: &Path,
+    prefix: &str,
+) -> Result<(), MetamorphicError> {
+    if create_dir_all(folder).is_err() {
+        return Err(MetamorphicError::Write(folder.to_path_buf()));
+    }
+    // Write ADG to file
+    adg.write_self_to_file(
+        Some(folder.to_string_lossy().into_owned()),
+        Some(format!("{prefix}_adg")),
+    )?;
+    adg.write_json_to_file(&folder.join(format!("{prefix}_adg.json")))?;
+    let strata_path = folder.join(format!("{prefix}_strata.txt"));
+    if std::fs::write(&strata_path, adg.stratify()?.to_string()).is_err() {
+        return Err(MetamorphicError::Write(strata_path));
+    }
+    // Write program to file
+    write_program_and_imports(program, adg, &folder.join(format!("{prefix}_program.rls")))
+}
+
+/// Write the program to a file. If transformations changed imported data,
+/// the changed data is written to the `data` folder next to it and
+/// the imports of the written program read from there.
+pub fn write_program_and_imports(
+    program: &ProgramHandle,
+    adg: &AnnotatedDependencyGraph,
+    path: &Path,
+) -> Result<(), MetamorphicError> {
+    if write_program_handle_to_file(program, path).is_err() {
+        return Err(MetamorphicError::Write(path.to_path_buf()));
+    }
+    if !adg.has_modified_imports() {
+        return Ok(());
+    }
+    let data_folder: PathBuf = path
+        .parent()
+        .map(|parent| parent.join("data"))
+        .unwrap_or(PathBuf::from("data"));
+    let copies: Vec<(String, PathBuf)> = adg.write_modified_imports(&data_folder)?;
+    let Ok(program_str) = std::fs::read_to_string(path) else {
+        return Err(MetamorphicError::ReadFile(path.to_path_buf()));
+    };
+    let mut redirected: String = String::new();
+    for line in program_str.lines() {
+        let mut line: String = line.to_string();
+        if line.trim_start().starts_with("@import") {
+            for (resource, copy) in &copies {
+                line = line.replace(
+                    &format!("\"{resource}\""),
+                    &format!("\"{}\"", copy.display()),
+                );
+            }
+        }
+        redirected += &line;
+        redirected.push('\n');
+    }
+    if std::fs::write(path, redirected).is_err() {
+        return Err(MetamorphicError::Write(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// Write program to a file
+pub fn write_program_handle_to_file(program: &ProgramHandle, path: &Path) -> std::io::Result<()> {
+    // Materialize the program
+    let program = program.materialize();
+    let to_str = program.to_string();
+
+    // Create a file to write to
+    let mut buffer: File = File::create(path)?;
+
+    // Write bytes
+    let mut pos = 0;
+    let data = to_str.as_bytes();
+    while pos < data.len() {
+        let bytes_written = buffer.write(&data[pos..])?;
+        pos += bytes_written;
+    }
+
+    Ok(())
+}
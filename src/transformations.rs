@@ -1,35 +1,61 @@
 use rand_chacha::ChaCha8Rng;
 
-use crate::transformations::{
-    annotated_dependency_graphs::AnnotatedDependencyGraph,
-    transformation_types::TransformationTypes,
+use crate::{
+    error::MetamorphicError,
+    transformations::{
+        annotated_dependency_graphs::AnnotatedDependencyGraph,
+        transformation_types::TransformationTypes,
+    },
 };
 
 pub mod add_fact_node_and_edge;
+pub mod add_imported_row;
+pub mod add_redundant_body_atom;
 pub mod add_relational_node;
+pub mod add_rule_edge;
 pub mod annotated_dependency_graphs;
+pub mod duplicate_rule;
+pub mod fold_predicate;
 pub mod hello_world;
 pub mod name_rules;
+pub mod remove_fact;
+pub mod remove_imported_row;
+pub mod reorder_and_rename_rule;
 pub mod select_random_output_predicate;
+pub mod serialization;
 pub mod testing_transformation;
 pub mod transformation_manager;
+pub mod transformation_step;
 pub mod transformation_types;
+pub mod unfold_rule;
 mod util;
 // pub mod testing_transformation;
 
 /// Trait that defines a metamorphic transformation
 /// Includes a constructor "new" and a test for
-/// if this transformation can be applied under the current oracle
+/// if this transformation can be applied under the current oracle.
+/// Transformations that keep the program equivalent ignore the intended
+/// transformation type: equivalent programs are also contained in each other.
 pub trait MetamorphicTransformation<'a, 'b> {
     /// Fetch the ADG.
     // fn fetch_adg(self) -> &'a mut AnnotatedDependencyGraph;
     /// Initialise myself with references to rng and adg if I can be applied under the intended transformation type.
-    /// If I can't currently be applied, return None
+    /// If I can't currently be applied, return None.
+    /// Return an error if the ADG is in an unexpected state.
     fn new(
         adg: &'a mut AnnotatedDependencyGraph,
         rng: &'b mut ChaCha8Rng,
         intended_transformation_type: TransformationTypes,
-    ) -> Option<Self>
+    ) -> Result<Option<Self>, MetamorphicError>
+    where
+        Self: Sized;
+    /// Whether I can currently be applied under the intended transformation type,
+    /// without drawing random numbers.
+    /// Return an error if the ADG is in an unexpected state.
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        intended_transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError>
     where
         Self: Sized;
 }
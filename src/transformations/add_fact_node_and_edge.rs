@@ -12,15 +12,18 @@ use nemo::rule_model::pipeline::commit::ProgramCommit;
 use nemo::rule_model::programs::handle::ProgramHandle;
 
 use nemo::rule_model::pipeline::transformations::ProgramTransformation;
-use nemo::rule_model::programs::{ProgramRead, ProgramWrite};
+use nemo::rule_model::programs::ProgramWrite;
 
 use nemo::term_list;
 use rand::seq::{IndexedRandom, IteratorRandom};
+use petgraph::graph::NodeIndex;
 use rand::{Rng, RngCore};
 
+use crate::error::MetamorphicError;
 use crate::transformations::annotated_dependency_graphs::{
-    ADGNode, ADGRelationalNode, AnnotatedDependencyGraph,
+    ADGFactNode, ADGNode, ADGRelationalNode, AnnotatedDependencyGraph, ColumnType,
 };
+use crate::transformations::transformation_step::TransformationStep;
 use crate::transformations::transformation_types::TransformationTypes;
 use crate::transformations::MetamorphicTransformation;
 
@@ -31,6 +34,46 @@ pub struct AddFactNodeAndEdge<'a, 'b> {
     adg: &'a mut AnnotatedDependencyGraph,
     rng: &'b mut rand_chacha::ChaCha8Rng,
     chosen_to_rel_node: Tag,
+    chosen_to_rel_node_index: NodeIndex,
+    /// The terms of the fact when replaying a recorded step
+    planned_terms: Option<Vec<GroundTerm>>,
+}
+
+impl<'a, 'b> AddFactNodeAndEdge<'a, 'b> {
+    /// The relational nodes a fact can be added to under the transformation type
+    fn eligible_rel_nodes(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<Tag> {
+        match transformation_type {
+            TransformationTypes::EQU => adg.get_none_ancestry_relational_nodes(),
+            TransformationTypes::CON => adg.get_leq_negative_ancestry_relational_nodes(),
+            TransformationTypes::EXP => adg.get_leq_positive_ancestry_relational_nodes(),
+        }
+    }
+
+    /// Add the fact of a recorded step.
+    /// Returns None if the relation does not exist (anymore) or
+    /// adding a fact to it does not fit the transformation type.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+        chosen_to_rel_node: Tag,
+        terms: Vec<GroundTerm>,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        if !Self::eligible_rel_nodes(adg, transformation_type).contains(&chosen_to_rel_node) {
+            return Ok(None);
+        }
+        let chosen_to_rel_node_index: NodeIndex = adg.get_rel_node_tag(&chosen_to_rel_node)?;
+        Ok(Some(Self {
+            adg,
+            rng,
+            chosen_to_rel_node,
+            chosen_to_rel_node_index,
+            planned_terms: Some(terms),
+        }))
+    }
 }
 
 impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddFactNodeAndEdge<'a, 'b> {
@@ -41,33 +84,27 @@ impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddFactNodeAndEdge<'a, 'b> {
         adg: &'a mut AnnotatedDependencyGraph,
         rng: &'b mut rand_chacha::ChaCha8Rng,
         transformation_type: TransformationTypes,
-    ) -> Option<Self> {
-        match transformation_type {
-            TransformationTypes::EQU => Some(Self {
-                chosen_to_rel_node: adg
-                    .get_none_ancestry_relational_nodes()
-                    .choose(rng)?
-                    .clone(),
-                adg: adg,
-                rng: rng,
-            }),
-            TransformationTypes::CON => Some(Self {
-                chosen_to_rel_node: adg
-                    .get_leq_negative_ancestry_relational_nodes()
-                    .choose(rng)?
-                    .clone(),
-                adg: adg,
-                rng: rng,
-            }),
-            TransformationTypes::EXP => Some(Self {
-                chosen_to_rel_node: adg
-                    .get_leq_positive_ancestry_relational_nodes()
-                    .choose(rng)?
-                    .clone(),
-                adg: adg,
-                rng: rng,
-            }),
-        }
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(chosen_to_rel_node) = Self::eligible_rel_nodes(adg, transformation_type)
+            .choose(rng)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            chosen_to_rel_node_index: adg.get_rel_node_tag(&chosen_to_rel_node)?,
+            chosen_to_rel_node,
+            adg: adg,
+            rng: rng,
+            planned_terms: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_rel_nodes(adg, transformation_type).is_empty())
     }
 }
 
@@ -80,64 +117,93 @@ impl<'a, 'b> ProgramTransformation for AddFactNodeAndEdge<'a, 'b> {
         // Construct a fact tuple (Vec<Term>) of the correct arity
         //println!("{:#?}",program.arities());
         //println!("{:#?}",&self.chosen_to_rel_node);
-        let arities = program.arities();
-        let arity: Option<&usize> = arities.get(&self.chosen_to_rel_node);
-        // If the relation is new it does not have an arity yet. Then we
-        // randomly assign it an arity, which hopefully after we add the
-        // fact to the commit the program stores.
-        let arity : usize = *arity.unwrap_or(&self.rng.random_range(1..6));
         let mut terms: Vec<Term> = Vec::new();
-        for _index in 0..arity {
-            match self.rng.random_bool(0.5) {
-                // existing constant
-                true => match self.adg.get_ground_terms().choose(self.rng) {
-                    None => {
-                        // New constant instead
-                        let new_gt = self.adg.get_and_register_new_integer_constant(self.rng);
-                        terms.push(Term::Primitive(Primitive::Ground(new_gt)));
-                    }
-                    Some(gt) => terms.push(Term::Primitive(Primitive::Ground(gt.clone()))),
-                },
-                // new constant
-                false => {
-                    match self.rng.random_bool(0.5) {
-                        // new constant name
-                        true => {
-                            let new_gt = self.adg.get_and_register_new_string_constant(self.rng);
-                            terms.push(Term::Primitive(Primitive::Ground(new_gt)));
+        match &self.planned_terms {
+            // Replaying a recorded step
+            Some(planned_terms) => {
+                for gt in planned_terms {
+                    self.adg.register_ground_term(gt);
+                    terms.push(Term::Primitive(Primitive::Ground(gt.clone())));
+                }
+            }
+            None => {
+                // If the relation is new it does not have an arity yet. Then we
+                // randomly assign it an arity with columns of unknown type.
+                let column_types: Vec<ColumnType> =
+                    match self.adg.get_column_types(&self.chosen_to_rel_node) {
+                        Ok(Some(column_types)) => column_types,
+                        Ok(None) => vec![ColumnType::Unknown; self.rng.random_range(1..6)],
+                        Err(error) => {
+                            self.adg.record_failed_update(format!(
+                                "Chosen relational node vanished from the ADG: {error}"
+                            ));
+                            return commit.submit();
                         }
-                        // new integer
-                        false => {
-                            let new_gt = self.adg.get_and_register_new_integer_constant(self.rng);
-                            terms.push(Term::Primitive(Primitive::Ground(new_gt)));
+                    };
+                for column_type in column_types {
+                    let existing: Vec<GroundTerm> = self
+                        .adg
+                        .get_ground_terms()
+                        .iter()
+                        .filter(|gt| column_type.fits(ColumnType::of_ground_term(gt)))
+                        .cloned()
+                        .collect();
+                    let reuse: bool = self.rng.random_bool(0.5);
+                    let gt: GroundTerm = match existing.choose(self.rng) {
+                        // existing constant
+                        Some(gt) if reuse => gt.clone(),
+                        // new constant of the column's type, any type if it does not have one
+                        _ => {
+                            let new_type: ColumnType = match column_type {
+                                ColumnType::Unknown | ColumnType::Any => {
+                                    *[ColumnType::String, ColumnType::Int]
+                                        .choose(self.rng)
+                                        .unwrap()
+                                }
+                                column_type => column_type,
+                            };
+                            self.adg.get_and_register_new_constant(new_type, self.rng)
                         }
-                    }
+                    };
+                    terms.push(Term::Primitive(Primitive::Ground(gt)));
                 }
             }
         }
 
-        // Build name of the fact node
-        let mut terms_str = String::from("(");
-        let mut first = true;
-        for term in terms.clone() {
-            if !first {
-                terms_str += ", ";
-                first = false;
-            }
-            terms_str += &term.to_string();
-        }
-        terms_str += ")";
-
-        //let new_tuple : Tuple = Tuple::new([terms]);
-        //let new_rule: Rule = Rule::new(vec![Atom::new(self.chosen_to_rel_node,[terms])], Vec::new());
+        let ground_terms: Vec<GroundTerm> = terms
+            .iter()
+            .filter_map(|term| match term {
+                Term::Primitive(Primitive::Ground(gt)) => Some(gt.clone()),
+                _ => None,
+            })
+            .collect();
         let fact: Fact = Fact::new(self.chosen_to_rel_node.clone(), terms);
         commit.add_fact(fact);
-        let fact_node = self.adg.add_fact_node(terms_str.clone());
-        self.adg.add_fact_edge(
-            fact_node,
-            self.adg.get_rel_node_tag(&self.chosen_to_rel_node),
+        let fact_node = self
+            .adg
+            .add_fact_node(ADGFactNode::Fact(ground_terms.clone()));
+        self.adg
+            .add_fact_edge(fact_node, self.chosen_to_rel_node_index);
+        let column_types: Vec<ColumnType> = ground_terms
+            .iter()
+            .map(ColumnType::of_ground_term)
+            .collect();
+        if let Err(error) = self
+            .adg
+            .merge_column_types(&self.chosen_to_rel_node, &column_types)
+        {
+            self.adg.record_failed_update(format!(
+                "Failed to update the column types after adding a fact: {error}"
+            ));
+        }
+        println!(
+            "Added new fact node {}",
+            ADGFactNode::Fact(ground_terms.clone())
         );
-        println!("Added new fact node {}", terms_str);
+        self.adg.record_step(TransformationStep::AddFactNodeAndEdge {
+            chosen_to_rel_node: self.chosen_to_rel_node,
+            terms: ground_terms,
+        });
 
         commit.submit()
     }
@@ -0,0 +1,173 @@
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::commit::ProgramCommit;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use nemo::datavalues::DataValue;
+use rand::Rng;
+use rand::seq::IndexedRandom;
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::{AnnotatedDependencyGraph, ColumnType};
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+
+/// Add a row to the loaded data of an imported file.
+/// The program stays the same, the modified file is written next to it.
+/// Oracle depends on ancestry of all relational nodes that import the file,
+/// like for `AddFactNodeAndEdge`.
+pub struct AddImportedRow<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    rng: &'b mut rand_chacha::ChaCha8Rng,
+    resource: String,
+    /// The row when replaying a recorded step
+    planned_row: Option<Vec<String>>,
+}
+
+impl<'a, 'b> AddImportedRow<'a, 'b> {
+    /// The relational nodes a tuple can be added to under the transformation type
+    fn eligible_rel_nodes(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<Tag> {
+        match transformation_type {
+            TransformationTypes::EQU => adg.get_none_ancestry_relational_nodes(),
+            TransformationTypes::CON => adg.get_leq_negative_ancestry_relational_nodes(),
+            TransformationTypes::EXP => adg.get_leq_positive_ancestry_relational_nodes(),
+        }
+    }
+
+    /// The resources with loaded data whose importing relations are all eligible
+    fn eligible_resources(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<String> {
+        let eligible_rel_nodes: Vec<Tag> = Self::eligible_rel_nodes(adg, transformation_type);
+        adg.get_imported_resources()
+            .into_iter()
+            .filter(|(_, tags)| {
+                !tags.is_empty() && tags.iter().all(|tag| eligible_rel_nodes.contains(tag))
+            })
+            .map(|(resource, _)| resource)
+            .collect()
+    }
+
+    /// Add the row of a recorded step.
+    /// Returns None if the resource has no loaded data or
+    /// adding a row to it does not fit the transformation type.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+        resource: String,
+        row: Vec<String>,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        if !Self::eligible_resources(adg, transformation_type).contains(&resource) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            adg,
+            rng,
+            resource,
+            planned_row: Some(row),
+        }))
+    }
+
+    /// A random row, each field either taken from the same column of
+    /// an existing row or a new constant of the column's type
+    fn random_row(&mut self) -> Result<Vec<String>, MetamorphicError> {
+        let Some(data) = self.adg.get_imported_data(&self.resource) else {
+            return Err(MetamorphicError::UnknownImport(self.resource.clone()));
+        };
+        let column_types: Vec<ColumnType> = data.column_types().to_vec();
+        let rows: Vec<Vec<String>> = data.rows().to_vec();
+        let mut row: Vec<String> = Vec::new();
+        for (column, column_type) in column_types.into_iter().enumerate() {
+            let existing: Vec<&String> = rows.iter().filter_map(|row| row.get(column)).collect();
+            let reuse: bool = self.rng.random_bool(0.5);
+            let field: String = match existing.choose(self.rng) {
+                // existing value
+                Some(field) if reuse => (*field).clone(),
+                // new constant of the column's type, any type if it does not have one
+                _ => {
+                    let new_type: ColumnType = match column_type {
+                        ColumnType::Unknown | ColumnType::Any => {
+                            *[ColumnType::String, ColumnType::Int]
+                                .choose(self.rng)
+                                .unwrap()
+                        }
+                        column_type => column_type,
+                    };
+                    self.adg
+                        .get_and_register_new_constant(new_type, self.rng)
+                        .value()
+                        .lexical_value()
+                }
+            };
+            row.push(field);
+        }
+        Ok(row)
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddImportedRow<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(resource) = Self::eligible_resources(adg, transformation_type)
+            .choose(rng)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            rng,
+            resource,
+            planned_row: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_resources(adg, transformation_type).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for AddImportedRow<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        // The program stays the same, only the data changes
+        let commit: ProgramCommit = program.fork_full();
+
+        let row: Vec<String> = match self.planned_row.take() {
+            Some(row) => row,
+            None => match self.random_row() {
+                Ok(row) => row,
+                Err(error) => {
+                    self.adg.record_failed_update(format!(
+                        "Chosen import vanished from the ADG: {error}"
+                    ));
+                    return commit.submit();
+                }
+            },
+        };
+        if let Err(error) = self.adg.add_imported_row(&self.resource, row.clone()) {
+            self.adg.record_failed_update(format!(
+                "Failed to update the ADG after adding a row: {error}"
+            ));
+        }
+        println!("Added row ({}) to {}", row.join(", "), self.resource);
+        self.adg.record_step(TransformationStep::AddImportedRow {
+            resource: self.resource,
+            row,
+        });
+
+        commit.submit()
+    }
+}
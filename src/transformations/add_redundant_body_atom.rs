@@ -0,0 +1,357 @@
+use nemo::rule_model::components::atom::Atom;
+use nemo::rule_model::components::literal::Literal;
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::components::term::Term;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::{AnnotatedDependencyGraph, Sign};
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util;
+
+/// Add a copy of a positive body atom to the body of a rule.
+/// The copy is either exact or, if the atom is over the relation of a head atom,
+/// has some arguments replaced by fresh variables, which joins the head's relation
+/// with itself. Every match of the body also matches the copy, so the program stays equivalent.
+/// The copy gets an edge parallel to the edge of the copied atom, so
+/// ancestry and strata stay the same.
+/// Rules with aggregates are left alone, the fresh variables could change their groups.
+pub struct AddRedundantBodyAtom<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    rng: &'b mut rand_chacha::ChaCha8Rng,
+    rule_name: String,
+    /// The redundant atom when replaying a recorded step
+    planned: Option<RedundantAtom>,
+}
+
+/// Which body atom is copied, how and where the copy is inserted into the body
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RedundantAtom {
+    /// Position of the copied atom in the body
+    pub copied_index: usize,
+    pub copy: AtomCopy,
+    /// Position of the copy in the new body
+    pub insert_index: usize,
+}
+
+/// How the copy differs from the copied atom
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AtomCopy {
+    /// The copy is the same atom
+    Exact,
+    /// The copied atom is over the relation of a head atom and the arguments
+    /// at these positions are replaced by fresh variables
+    HeadSelfJoin { generalized: Vec<usize> },
+}
+
+impl<'a, 'b> AddRedundantBodyAtom<'a, 'b> {
+    /// The rules with a positive body atom and without aggregates
+    fn eligible_rules(adg: &AnnotatedDependencyGraph) -> Vec<String> {
+        adg.get_rule_names(false)
+            .into_iter()
+            .filter(|rule_name| {
+                let edges = adg.get_rule_edges(rule_name);
+                edges
+                    .iter()
+                    .any(|(_, _, rel_edge)| rel_edge.sign == Sign::Positive)
+                    && edges
+                        .iter()
+                        .all(|(_, _, rel_edge)| !rel_edge.features.is_non_monotone())
+            })
+            .collect()
+    }
+
+    /// Add the atom of a recorded step.
+    /// Returns None if the rule does not exist (anymore) or can't get a redundant atom.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        rule_name: String,
+        redundant_atom: RedundantAtom,
+    ) -> Option<Self> {
+        if !Self::eligible_rules(adg).contains(&rule_name) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: Some(redundant_atom),
+        })
+    }
+
+    /// With probability 1/2, if the body has positive atoms with arguments over
+    /// the relation of a head atom, copy one of them and generalize each argument with
+    /// probability 1/2, but at least one. Otherwise copy a random positive body atom exactly.
+    /// The copy is inserted at a random position.
+    /// None if the rule has no positive body atom.
+    fn plan_redundant_atom(&mut self, rule: &Rule) -> Option<RedundantAtom> {
+        let positive: Vec<usize> = (0..rule.body().len())
+            .filter(|index| matches!(rule.body()[*index], Literal::Positive(_)))
+            .collect();
+        let over_head: Vec<usize> = positive
+            .iter()
+            .copied()
+            .filter(|index| match &rule.body()[*index] {
+                Literal::Positive(atom) => {
+                    atom.terms().next().is_some() && is_over_head(rule, atom)
+                }
+                _ => false,
+            })
+            .collect();
+        let (copied_index, copy) = match !over_head.is_empty() && self.rng.random_bool(0.5) {
+            true => {
+                let copied_index: usize = *over_head.choose(self.rng)?;
+                let Literal::Positive(atom) = &rule.body()[copied_index] else {
+                    return None;
+                };
+                let always: usize = self.rng.random_range(0..atom.len());
+                let generalized: Vec<usize> = (0..atom.len())
+                    .filter(|position| *position == always || self.rng.random_bool(0.5))
+                    .collect();
+                (copied_index, AtomCopy::HeadSelfJoin { generalized })
+            }
+            false => (*positive.choose(self.rng)?, AtomCopy::Exact),
+        };
+        Some(RedundantAtom {
+            copied_index,
+            copy,
+            insert_index: self.rng.random_range(0..=rule.body().len()),
+        })
+    }
+}
+
+/// Whether the atom is over the relation of a head atom of the rule
+fn is_over_head(rule: &Rule, atom: &Atom) -> bool {
+    let predicate: Tag = atom.predicate();
+    rule.head()
+        .iter()
+        .any(|head_atom| head_atom.predicate() == predicate)
+}
+
+/// The rule with the redundant atom added to its body.
+/// None if the atom to be copied is not a positive body atom of the rule,
+/// the copy can't be inserted at the position, or a self-join over the head
+/// copies an atom that is not over the head's relation or generalizes no
+/// or non-existing arguments.
+fn add_redundant_atom(rule: &Rule, redundant_atom: &RedundantAtom) -> Option<Rule> {
+    let Some(Literal::Positive(atom)) = rule.body().get(redundant_atom.copied_index) else {
+        return None;
+    };
+    if redundant_atom.insert_index > rule.body().len() {
+        return None;
+    }
+    let mut copy: Atom = atom.clone();
+    if let AtomCopy::HeadSelfJoin { generalized } = &redundant_atom.copy {
+        if !is_over_head(rule, atom)
+            || generalized.is_empty()
+            || generalized.iter().any(|position| *position >= atom.len())
+        {
+            return None;
+        }
+        let prefix: String = util::fresh_variable_prefix(rule);
+        let mut fresh: usize = 0;
+        for (position, term) in copy.terms_mut().enumerate() {
+            if generalized.contains(&position) {
+                *term = Term::universal_variable(&format!("{prefix}_{fresh}"));
+                fresh += 1;
+            }
+        }
+    }
+    let mut extended: Rule = rule.clone();
+    extended
+        .body_mut()
+        .insert(redundant_atom.insert_index, Literal::Positive(copy));
+    Some(extended)
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddRedundantBodyAtom<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(rule_name) = Self::eligible_rules(adg).choose(rng).cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_rules(adg).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for AddRedundantBodyAtom<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), program) else {
+            println!("Rule {} has vanished from the program", self.rule_name);
+            return program.fork_full().submit();
+        };
+        let planned: Option<RedundantAtom> = match self.planned.take() {
+            Some(planned) => Some(planned),
+            None => self.plan_redundant_atom(rule),
+        };
+        let Some((redundant_atom, extended)) = planned.and_then(|redundant_atom| {
+            let extended: Rule = add_redundant_atom(rule, &redundant_atom)?;
+            Some((redundant_atom, extended))
+        }) else {
+            println!("Rule {} has no fitting body atom to copy", self.rule_name);
+            return program.fork_full().submit();
+        };
+
+        let new_program: ProgramHandle =
+            util::replace_rule(program, &self.rule_name, std::slice::from_ref(&extended))?;
+
+        // The positions of the body atoms have changed, so all edges of the rule are rebuilt
+        self.adg.remove_rule_edges(&self.rule_name);
+        if let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), &new_program) {
+            if let Err(error) = self.adg.add_rule_edges(rule) {
+                self.adg.record_failed_update(format!(
+                    "Failed to add the edges of an extended rule to the ADG: {error}"
+                ));
+            }
+        }
+        println!("Extended rule {} to {}", self.rule_name, extended);
+        self.adg
+            .record_step(TransformationStep::AddRedundantBodyAtom {
+                rule_name: self.rule_name,
+                redundant_atom,
+            });
+
+        Ok(new_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::rule_model::components::{
+        atom::Atom, literal::Literal, rule::Rule, tag::Tag, term::Term,
+    };
+
+    use super::{AtomCopy, RedundantAtom, add_redundant_atom};
+
+    fn atom(predicate: &str, variables: &[&str]) -> Atom {
+        Atom::new(
+            Tag::new(predicate.to_string()),
+            variables.iter().map(|name| Term::universal_variable(name)),
+        )
+    }
+
+    /// `p(?x, ?y) :- p(?x, ?z), e(?z, ?y)`
+    fn recursive_rule() -> Rule {
+        Rule::new(
+            vec![atom("p", &["x", "y"])],
+            vec![
+                Literal::Positive(atom("p", &["x", "z"])),
+                Literal::Positive(atom("e", &["z", "y"])),
+            ],
+        )
+    }
+
+    fn redundant_atom(copied_index: usize, copy: AtomCopy, insert_index: usize) -> RedundantAtom {
+        RedundantAtom {
+            copied_index,
+            copy,
+            insert_index,
+        }
+    }
+
+    #[test]
+    fn exact_copy_is_inserted_at_the_position() {
+        let rule = recursive_rule();
+        let extended = add_redundant_atom(&rule, &redundant_atom(1, AtomCopy::Exact, 0))
+            .expect("e(?z, ?y) is a positive body atom");
+        assert_eq!(
+            extended.body(),
+            &vec![
+                Literal::Positive(atom("e", &["z", "y"])),
+                Literal::Positive(atom("p", &["x", "z"])),
+                Literal::Positive(atom("e", &["z", "y"])),
+            ]
+        );
+        assert_eq!(extended.head(), rule.head());
+    }
+
+    #[test]
+    fn copy_can_be_appended_to_the_body() {
+        let rule = recursive_rule();
+        let extended = add_redundant_atom(&rule, &redundant_atom(0, AtomCopy::Exact, 2))
+            .expect("the copy may follow the last body literal");
+        assert_eq!(extended.body().len(), 3);
+        assert_eq!(
+            extended.body()[2],
+            Literal::Positive(atom("p", &["x", "z"]))
+        );
+    }
+
+    #[test]
+    fn head_self_join_generalizes_the_positions() {
+        let rule = recursive_rule();
+        let copy = AtomCopy::HeadSelfJoin {
+            generalized: vec![1],
+        };
+        let extended = add_redundant_atom(&rule, &redundant_atom(0, copy, 2))
+            .expect("p(?x, ?z) is over the head's relation");
+        assert_eq!(&extended.body()[..2], &rule.body()[..]);
+        assert_eq!(
+            extended.body()[2],
+            Literal::Positive(atom("p", &["x", "v_0"]))
+        );
+    }
+
+    #[test]
+    fn head_self_join_needs_an_atom_over_the_head() {
+        let rule = recursive_rule();
+        let copy = AtomCopy::HeadSelfJoin {
+            generalized: vec![0],
+        };
+        assert!(add_redundant_atom(&rule, &redundant_atom(1, copy, 0)).is_none());
+    }
+
+    #[test]
+    fn head_self_join_needs_existing_generalized_positions() {
+        let rule = recursive_rule();
+        for generalized in [vec![], vec![2]] {
+            let copy = AtomCopy::HeadSelfJoin { generalized };
+            assert!(add_redundant_atom(&rule, &redundant_atom(0, copy, 0)).is_none());
+        }
+    }
+
+    #[test]
+    fn out_of_range_positions_are_rejected() {
+        let rule = recursive_rule();
+        assert!(add_redundant_atom(&rule, &redundant_atom(2, AtomCopy::Exact, 0)).is_none());
+        assert!(add_redundant_atom(&rule, &redundant_atom(0, AtomCopy::Exact, 3)).is_none());
+    }
+
+    #[test]
+    fn only_positive_atoms_are_copied() {
+        let rule = Rule::new(
+            vec![atom("p", &["x"])],
+            vec![
+                Literal::Positive(atom("e", &["x"])),
+                Literal::Negative(atom("f", &["x"])),
+            ],
+        );
+        assert!(add_redundant_atom(&rule, &redundant_atom(1, AtomCopy::Exact, 0)).is_none());
+    }
+}
@@ -8,8 +8,10 @@ use nemo::rule_model::programs::ProgramRead;
 
 use rand::RngCore;
 
+use crate::error::MetamorphicError;
 use crate::transformations::MetamorphicTransformation;
 use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
+use crate::transformations::transformation_step::TransformationStep;
 use crate::transformations::transformation_types::TransformationTypes;
 
 /// Add a relational node with a new relational name and no
@@ -18,31 +20,69 @@ use crate::transformations::transformation_types::TransformationTypes;
 /// in the program.
 pub struct AddRelationalNode<'a, 'b> {
     adg: &'a mut AnnotatedDependencyGraph,
-    rng: &'b mut rand_chacha::ChaCha8Rng,
+    _rng: &'b mut rand_chacha::ChaCha8Rng,
+    new_relation: Tag,
+}
+
+impl<'a, 'b> AddRelationalNode<'a, 'b> {
+    /// Add the relational node of a recorded step.
+    /// Returns None if the relation already exists.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        new_relation: Tag,
+    ) -> Option<Self> {
+        if adg.has_rel_node(&new_relation) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            _rng: rng,
+            new_relation,
+        })
+    }
 }
 
 impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddRelationalNode<'a, 'b> {
     /* fn fetch_adg(self) -> &'a mut AnnotatedDependencyGraph {
         self.adg
     } */
-    fn new(adg: &'a mut AnnotatedDependencyGraph, rng: &'b mut rand_chacha::ChaCha8Rng, _transformation_type : TransformationTypes) -> Option<Self> {
-        Some(Self { adg, rng })
+    fn new(adg: &'a mut AnnotatedDependencyGraph, rng: &'b mut rand_chacha::ChaCha8Rng, _transformation_type : TransformationTypes) -> Result<Option<Self>, MetamorphicError> {
+        let new_relation: Tag = Tag::new(adg.get_new_relation_name(rng));
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            new_relation,
+        }))
+    }
+    /// A new relation can always be added
+    fn is_applicable(
+        _adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(true)
     }
-    
 }
 
 impl<'a, 'b> ProgramTransformation for AddRelationalNode<'a, 'b> {
     fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
         //let commit = program.fork();
         let commit: ProgramCommit = program.fork_full();
-        let new_relation_name: String = self.adg.get_new_relation_name(self.rng);
         // No rule yet, will introduce these later
         // let new_rule: Rule = Rule::new(vec![head.clone()], rule.body().clone());
 
         // Add a new relational node
-        let tag: Tag = Tag::new(new_relation_name);
-        self.adg.add_rel_node(&tag);
+        let tag: Tag = self.new_relation;
+        let node = self.adg.add_rel_node(&tag);
+        // Nothing depends on the new relation yet
+        if let Err(error) = self.adg.update_ancestry_and_inverse_stratum(node) {
+            self.adg.record_failed_update(format!(
+                "Failed to update the ADG after adding a relation: {error}"
+            ));
+        }
         println!("Added new relation of name {}", tag);
+        self.adg
+            .record_step(TransformationStep::AddRelationalNode { relation: tag });
         
         commit.submit()
     }
@@ -0,0 +1,441 @@
+use std::fmt::{Display, Formatter};
+
+use nemo::rule_model::components::atom::Atom;
+use nemo::rule_model::components::literal::Literal;
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::components::term::Term;
+use nemo::rule_model::components::term::primitive::Primitive;
+use nemo::rule_model::components::term::primitive::ground::GroundTerm;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::commit::ProgramCommit;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::ProgramWrite;
+
+use petgraph::graph::NodeIndex;
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MetamorphicError;
+use crate::transformations::annotated_dependency_graphs::{
+    AnnotatedDependencyGraph, ColumnType, Sign,
+};
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::{MetamorphicTransformation, serialization, util};
+
+/// Maximal number of positive body atoms of a new rule
+static MAX_POSITIVE_BODY_ATOMS: usize = 2;
+
+/// A term of a planned rule
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedTerm {
+    Variable {
+        name: String,
+    },
+    Ground {
+        #[serde(with = "serialization::ground_term")]
+        value: GroundTerm,
+    },
+}
+impl PlannedTerm {
+    fn to_term(&self) -> Term {
+        match self {
+            Self::Variable { name } => Term::universal_variable(name),
+            Self::Ground { value } => Term::Primitive(Primitive::Ground(value.clone())),
+        }
+    }
+}
+impl Display for PlannedTerm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable { name } => write!(f, "?{name}"),
+            Self::Ground { value } => write!(f, "{value}"),
+        }
+    }
+}
+
+/// An atom of a planned rule
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlannedAtom {
+    #[serde(with = "serialization::tag")]
+    pub predicate: Tag,
+    pub terms: Vec<PlannedTerm>,
+}
+impl PlannedAtom {
+    fn to_atom(&self) -> Atom {
+        Atom::new(
+            self.predicate.clone(),
+            self.terms.iter().map(|term| term.to_term()),
+        )
+    }
+}
+impl Display for PlannedAtom {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|term| term.to_string()).collect();
+        write!(f, "{}({})", self.predicate, terms.join(", "))
+    }
+}
+
+/// A rule with a single head atom, as it is added by `AddRuleEdge`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlannedRule {
+    pub rule_name: String,
+    pub head: PlannedAtom,
+    pub positive_body: Vec<PlannedAtom>,
+    pub negative_body: Vec<PlannedAtom>,
+}
+impl PlannedRule {
+    fn to_rule(&self) -> Rule {
+        let mut body: Vec<Literal> = Vec::new();
+        for atom in &self.positive_body {
+            body.push(Literal::Positive(atom.to_atom()));
+        }
+        for atom in &self.negative_body {
+            body.push(Literal::Negative(atom.to_atom()));
+        }
+        let mut rule = Rule::new(vec![self.head.to_atom()], body);
+        rule.set_name(&self.rule_name);
+        rule
+    }
+
+    fn ground_terms(&self) -> impl Iterator<Item = &GroundTerm> {
+        std::iter::once(&self.head)
+            .chain(self.positive_body.iter())
+            .chain(self.negative_body.iter())
+            .flat_map(|atom| atom.terms.iter())
+            .filter_map(|term| match term {
+                PlannedTerm::Ground { value } => Some(value),
+                PlannedTerm::Variable { .. } => None,
+            })
+    }
+}
+impl Display for PlannedRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let body: Vec<String> = self
+            .positive_body
+            .iter()
+            .map(|atom| atom.to_string())
+            .chain(self.negative_body.iter().map(|atom| format!("~{atom}")))
+            .collect();
+        write!(
+            f,
+            "{}: {} :- {} .",
+            self.rule_name,
+            self.head,
+            body.join(", ")
+        )
+    }
+}
+
+/// Add a new safe rule from one or more existing body relations
+/// into an existing head relation, together with its relational edges.
+/// The head decides the oracle: a new rule only adds facts to its head.
+/// The body relations are chosen such that their ancestry fits the
+/// head and the program stays stratifiable.
+pub struct AddRuleEdge<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    rng: &'b mut rand_chacha::ChaCha8Rng,
+    head: (Tag, NodeIndex),
+    positive_body: Vec<(Tag, NodeIndex)>,
+    negative_body: Vec<(Tag, NodeIndex)>,
+    /// The rule when replaying a recorded step
+    planned_rule: Option<PlannedRule>,
+}
+
+impl<'a, 'b> AddRuleEdge<'a, 'b> {
+    /// The relational nodes that can be the head of a new rule under the transformation type
+    fn eligible_heads(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<Tag> {
+        match transformation_type {
+            TransformationTypes::EQU => adg.get_none_ancestry_relational_nodes(),
+            TransformationTypes::CON => adg.get_leq_negative_ancestry_relational_nodes(),
+            TransformationTypes::EXP => adg.get_leq_positive_ancestry_relational_nodes(),
+        }
+    }
+
+    /// The relational nodes that can be used in the body of a new rule with the given head.
+    /// A positive body relation inherits the ancestry of the head, a negative one its inverse.
+    /// Relations whose edge would close a cycle through a negative edge are excluded.
+    fn eligible_body(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+        head: NodeIndex,
+        sign: Sign,
+    ) -> Result<Vec<(Tag, NodeIndex)>, MetamorphicError> {
+        let candidates: Vec<Tag> = match (transformation_type, &sign) {
+            (TransformationTypes::EQU, _) => adg.get_none_ancestry_relational_nodes(),
+            (TransformationTypes::EXP, Sign::Positive)
+            | (TransformationTypes::CON, Sign::Negative) => {
+                adg.get_leq_positive_ancestry_relational_nodes()
+            }
+            (TransformationTypes::EXP, Sign::Negative)
+            | (TransformationTypes::CON, Sign::Positive) => {
+                adg.get_leq_negative_ancestry_relational_nodes()
+            }
+        };
+        let mut eligible: Vec<(Tag, NodeIndex)> = Vec::new();
+        for tag in candidates {
+            let node: NodeIndex = adg.get_rel_node_tag(&tag)?;
+            if !adg.would_break_stratification(node, head, sign.clone()) {
+                eligible.push((tag, node));
+            }
+        }
+        Ok(eligible)
+    }
+
+    /// The eligible heads that have at least one eligible positive body relation
+    fn heads_with_body(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<Vec<(Tag, NodeIndex)>, MetamorphicError> {
+        let mut heads: Vec<(Tag, NodeIndex)> = Vec::new();
+        for tag in Self::eligible_heads(adg, transformation_type.clone()) {
+            let head: NodeIndex = adg.get_rel_node_tag(&tag)?;
+            if !Self::eligible_body(adg, transformation_type.clone(), head, Sign::Positive)?
+                .is_empty()
+            {
+                heads.push((tag, head));
+            }
+        }
+        Ok(heads)
+    }
+
+    /// Add the rule of a recorded step.
+    /// Returns None if one of its relations does not exist (anymore) or
+    /// the rule does not fit the transformation type.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+        planned_rule: PlannedRule,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let head_tag: Tag = planned_rule.head.predicate.clone();
+        if !Self::eligible_heads(adg, transformation_type.clone()).contains(&head_tag) {
+            return Ok(None);
+        }
+        let head: NodeIndex = adg.get_rel_node_tag(&head_tag)?;
+        let mut body: Vec<Vec<(Tag, NodeIndex)>> = Vec::new();
+        for (atoms, sign) in [
+            (&planned_rule.positive_body, Sign::Positive),
+            (&planned_rule.negative_body, Sign::Negative),
+        ] {
+            let eligible = Self::eligible_body(adg, transformation_type.clone(), head, sign)?;
+            let mut chosen: Vec<(Tag, NodeIndex)> = Vec::new();
+            for atom in atoms {
+                match eligible.iter().find(|(tag, _)| *tag == atom.predicate) {
+                    None => return Ok(None),
+                    Some(relation) => chosen.push(relation.clone()),
+                }
+            }
+            body.push(chosen);
+        }
+        let negative_body = body.pop().unwrap_or_default();
+        let positive_body = body.pop().unwrap_or_default();
+        Ok(Some(Self {
+            adg,
+            rng,
+            head: (head_tag, head),
+            positive_body,
+            negative_body,
+            planned_rule: Some(planned_rule),
+        }))
+    }
+
+    /// Choose the terms of the rule: body atoms share variables,
+    /// head and negative atoms only use variables of positive atoms.
+    /// Variables are only shared between columns of fitting types.
+    fn plan_rule(&mut self, program: &ProgramHandle) -> PlannedRule {
+        // Relations without an arity (e.g. new ones) get one from the rule
+        let column_types_of = |tag: &Tag,
+                               adg: &mut AnnotatedDependencyGraph,
+                               rng: &mut rand_chacha::ChaCha8Rng|
+         -> Vec<ColumnType> {
+            match adg.get_column_types(tag) {
+                Ok(Some(column_types)) => column_types,
+                Ok(None) => {
+                    let column_types = vec![ColumnType::Unknown; rng.random_range(1..4)];
+                    if let Err(error) = adg.merge_column_types(tag, &column_types) {
+                        adg.record_failed_update(format!(
+                            "Failed to assign an arity to {tag}: {error}"
+                        ));
+                    }
+                    column_types
+                }
+                Err(error) => {
+                    adg.record_failed_update(format!(
+                        "Chosen relational node vanished from the ADG: {error}"
+                    ));
+                    Vec::new()
+                }
+            }
+        };
+
+        let mut variables: Vec<(String, ColumnType)> = Vec::new();
+        let mut positive_body: Vec<PlannedAtom> = Vec::new();
+        for (tag, _) in &self.positive_body {
+            let mut terms: Vec<PlannedTerm> = Vec::new();
+            for column_type in column_types_of(tag, self.adg, self.rng) {
+                // Join with an earlier variable or introduce a new one
+                let fitting: Vec<usize> = (0..variables.len())
+                    .filter(|index| variables[*index].1.fits(column_type))
+                    .collect();
+                let index: usize = match fitting.choose(self.rng) {
+                    Some(index) if self.rng.random_bool(0.5) => *index,
+                    _ => {
+                        variables.push((format!("x_{}", variables.len()), ColumnType::Unknown));
+                        variables.len() - 1
+                    }
+                };
+                variables[index].1 = variables[index].1.join(column_type);
+                terms.push(PlannedTerm::Variable {
+                    name: variables[index].0.clone(),
+                });
+            }
+            positive_body.push(PlannedAtom {
+                predicate: tag.clone(),
+                terms,
+            });
+        }
+
+        // Only bound variables, to keep the rule safe
+        let bound_atom = |tag: &Tag,
+                          adg: &mut AnnotatedDependencyGraph,
+                          rng: &mut rand_chacha::ChaCha8Rng|
+         -> PlannedAtom {
+            let mut terms: Vec<PlannedTerm> = Vec::new();
+            for column_type in column_types_of(tag, adg, rng) {
+                let fitting: Vec<&String> = variables
+                    .iter()
+                    .filter(|(_, variable_type)| variable_type.fits(column_type))
+                    .map(|(name, _)| name)
+                    .collect();
+                match fitting.choose(rng) {
+                    Some(name) => terms.push(PlannedTerm::Variable {
+                        name: (*name).clone(),
+                    }),
+                    // No bound variable of this type
+                    None => terms.push(PlannedTerm::Ground {
+                        value: adg.get_and_register_new_constant(column_type, rng),
+                    }),
+                }
+            }
+            PlannedAtom {
+                predicate: tag.clone(),
+                terms,
+            }
+        };
+        let head = bound_atom(&self.head.0, self.adg, self.rng);
+        let negative_body: Vec<PlannedAtom> = self
+            .negative_body
+            .iter()
+            .map(|(tag, _)| bound_atom(tag, self.adg, self.rng))
+            .collect();
+
+        PlannedRule {
+            rule_name: util::get_new_rule_name(program),
+            head,
+            positive_body,
+            negative_body,
+        }
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for AddRuleEdge<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some((head_tag, head)) = Self::heads_with_body(adg, transformation_type.clone())?
+            .choose(rng)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+
+        // At least one positive body atom, to keep the rule safe
+        let eligible_positive =
+            Self::eligible_body(adg, transformation_type.clone(), head, Sign::Positive)?;
+        let num_positive: usize = rng.random_range(1..=MAX_POSITIVE_BODY_ATOMS);
+        let positive_body: Vec<(Tag, NodeIndex)> = eligible_positive
+            .choose_multiple(rng, num_positive)
+            .cloned()
+            .collect();
+
+        let eligible_negative =
+            Self::eligible_body(adg, transformation_type, head, Sign::Negative)?;
+        let negative_body: Vec<(Tag, NodeIndex)> = match rng.random_bool(0.5) {
+            true => eligible_negative.choose(rng).cloned().into_iter().collect(),
+            false => Vec::new(),
+        };
+
+        Ok(Some(Self {
+            adg,
+            rng,
+            head: (head_tag, head),
+            positive_body,
+            negative_body,
+            planned_rule: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::heads_with_body(adg, transformation_type)?.is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for AddRuleEdge<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        // Copy the program
+        let mut commit: ProgramCommit = program.fork_full();
+
+        let planned_rule: PlannedRule = match self.planned_rule.take() {
+            // Replaying a recorded step
+            Some(planned_rule) => {
+                for gt in planned_rule.ground_terms() {
+                    self.adg.register_ground_term(gt);
+                }
+                planned_rule
+            }
+            None => self.plan_rule(program),
+        };
+        commit.add_rule(planned_rule.to_rule());
+        let new_program: ProgramHandle = commit.submit()?;
+
+        // The rule only has its id once it is part of the program
+        if let Some(rule) = util::fetch_rule_by_name(planned_rule.rule_name.clone(), &new_program) {
+            if let Err(error) = self.adg.add_rule_edges(rule) {
+                self.adg.record_failed_update(format!(
+                    "Failed to add the edges of a new rule to the ADG: {error}"
+                ));
+            }
+        }
+        // The head relation now also gets the column types of the body
+        if let Err(error) = self.adg.infer_column_types(&new_program) {
+            self.adg.record_failed_update(format!(
+                "Failed to update the column types after adding a rule: {error}"
+            ));
+        }
+        // The body relations now have the ancestry of the head
+        if let Err(error) = self.adg.update_ancestry_and_inverse_stratum(self.head.1) {
+            self.adg.record_failed_update(format!(
+                "Failed to update the ADG after adding a rule: {error}"
+            ));
+        }
+        println!("Added new rule {}", planned_rule);
+        self.adg
+            .record_step(TransformationStep::AddRuleEdge { rule: planned_rule });
+
+        Ok(new_program)
+    }
+}
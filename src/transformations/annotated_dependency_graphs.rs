@@ -1,20 +1,32 @@
 use std::{
-    collections::HashMap,
-    fmt::{Debug, Formatter},
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{Debug, Display, Formatter},
     iter::Filter,
-    process::exit,
+    path::{Path, PathBuf},
 };
 
+use nemo::datavalues::{AnyDataValue, DataValue, ValueDomain};
 use nemo::rule_model::{
     components::{
-        self, rule::Rule, statement, tag::Tag, term::primitive::ground::GroundTerm,
+        self,
+        atom::Atom,
+        fact::Fact,
+        import_export::ImportDirective,
+        literal::Literal,
+        rule::Rule,
+        statement,
+        tag::Tag,
+        term::{
+            primitive::{ground::GroundTerm, Primitive},
+            Term,
+        },
         ComponentIdentity, IterablePrimitives,
     },
     pipeline::id::ProgramComponentId,
     programs::{handle::ProgramHandle, ProgramRead},
 };
 use petgraph::{
-    dot::Dot,
+    algo::has_path_connecting,
     graph::{EdgeReference, NodeIndex},
 };
 use petgraph::{
@@ -24,8 +36,26 @@ use petgraph::{
 };
 use rand::RngCore;
 use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy)]
+use crate::{
+    error::MetamorphicError,
+    transformations::{
+        serialization::SerializedGroundTerm,
+        transformation_step::TransformationStep,
+        util::{fact_ground_terms, same_terms},
+    },
+};
+
+mod imported_data;
+mod rendering;
+mod stratification;
+
+pub use imported_data::ImportedData;
+pub use stratification::DependencyCycle;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Ancestry {
     Positive,
     Negative,
@@ -93,11 +123,95 @@ impl Ancestry {
     }
 }
 
+/// The type of the values in one column of a relation.
+/// Unknown if nothing is known about the column yet,
+/// Any if it holds values of different types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Unknown,
+    Int,
+    Double,
+    Float,
+    String,
+    Any,
+}
+impl ColumnType {
+    /// The most specific type that covers both types
+    pub fn join(self, other: Self) -> Self {
+        match (self, other) {
+            (ColumnType::Unknown, column_type) | (column_type, ColumnType::Unknown) => column_type,
+            _ if self == other => self,
+            _ => ColumnType::Any,
+        }
+    }
+    /// Whether values of both types can be in the same column
+    pub fn fits(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (ColumnType::Unknown | ColumnType::Any, _) | (_, ColumnType::Unknown | ColumnType::Any)
+        ) || self == other
+    }
+    pub fn of_ground_term(ground_term: &GroundTerm) -> Self {
+        let value: AnyDataValue = ground_term.value();
+        if value.to_i64().is_some() {
+            return ColumnType::Int;
+        }
+        match value.value_domain() {
+            ValueDomain::Int | ValueDomain::UnsignedLong => ColumnType::Int,
+            ValueDomain::Double => ColumnType::Double,
+            ValueDomain::Float => ColumnType::Float,
+            ValueDomain::PlainString | ValueDomain::LanguageTaggedString => ColumnType::String,
+            _ => ColumnType::Any,
+        }
+    }
+    /// The type of a value format of an `@import`, None for skipped columns
+    fn of_import_format(format: &str) -> Option<Self> {
+        match format {
+            "skip" => None,
+            "int" => Some(ColumnType::Int),
+            "double" => Some(ColumnType::Double),
+            "float" => Some(ColumnType::Float),
+            "string" => Some(ColumnType::String),
+            _ => Some(ColumnType::Any),
+        }
+    }
+}
+
+/// The printed value of an attribute of an import directive,
+/// followed by the rest of the directive, e.g. `"a.csv", format = (int)} .` for `resource`
+fn import_attribute<'s>(directive: &'s str, key: &str) -> Option<&'s str> {
+    directive.match_indices(key).find_map(|(start, _)| {
+        let preceded_by_name: bool = directive[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let value: &str = directive[start + key.len()..]
+            .trim_start()
+            .strip_prefix('=')?;
+        (!preceded_by_name).then_some(value.trim_start())
+    })
+}
+
+/// The column types given by the `format` of an import, if it has one.
+/// They are read from the printed directive, e.g. `format=(int, any)`.
+fn import_column_types(directive: &str) -> Option<Vec<ColumnType>> {
+    let value: &str = import_attribute(directive, "format")?.strip_prefix('(')?;
+    Some(
+        value[..value.find(')')?]
+            .split(',')
+            .filter_map(|format| ColumnType::of_import_format(format.trim()))
+            .collect(),
+    )
+}
+
 #[derive(Clone)]
 pub struct ADGRelationalNode {
     pub tag: Tag,
     pub inverse_stratum: Option<u32>,
     pub ancestry: Option<Ancestry>,
+    /// The type of each column, None while the arity is unknown
+    pub column_types: Option<Vec<ColumnType>>,
 }
 impl Debug for ADGRelationalNode {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -116,63 +230,133 @@ impl Debug for ADGRelationalNode {
     }
 }
 impl ADGRelationalNode {
-    /// Add incoming ancestry to myself, merging them
-    pub fn merge(&mut self, new_ancestry: Ancestry) {
-        match &self.ancestry {
-            None => self.ancestry = Some(new_ancestry),
-            Some(old_ancestry) => {
-                match old_ancestry {
-                    &Ancestry::Unknown => {
-                        (); /* no changes!*/
-                    }
-                    &Ancestry::None => self.ancestry = Some(new_ancestry),
-                    &Ancestry::Positive => match new_ancestry {
-                        Ancestry::Unknown => {
-                            println!(
-                                "Attempting to assign unknown ancestry. This is a bug I think"
-                            );
-                            exit(1);
-                        }
-                        Ancestry::None => {
-                            println!("Attempting to assign none ancestry. This is a bug I think");
-                            exit(1);
-                        }
-                        Ancestry::Negative => self.ancestry = Some(Ancestry::Unknown), /* Both pos and neg */
-                        Ancestry::Positive => (), /* Was already positive */
-                    },
-                    &Ancestry::Negative => {
-                        match new_ancestry {
-                            Ancestry::Unknown => {
-                                println!(
-                                    "Attempting to assign unknown ancestry. This is a bug I think"
-                                );
-                                exit(1);
-                            }
-                            Ancestry::None => {
-                                println!(
-                                    "Attempting to assign none ancestry. This is a bug I think"
-                                );
-                                exit(1);
-                            }
-                            Ancestry::Negative => (), /* Was already negative */
-                            Ancestry::Positive => self.ancestry = Some(Ancestry::Unknown), /* Both pos and neg */
-                        }
-                    }
+    /// Add incoming ancestry to myself, merging them.
+    /// Returns whether my ancestry changed.
+    pub fn merge(&mut self, new_ancestry: Ancestry) -> bool {
+        let merged: Ancestry = match self.ancestry {
+            None | Some(Ancestry::None) => new_ancestry,
+            Some(Ancestry::Unknown) => Ancestry::Unknown,
+            Some(old_ancestry) => match new_ancestry {
+                Ancestry::None => old_ancestry,
+                /* Was already positive or negative */
+                _ if new_ancestry == old_ancestry => old_ancestry,
+                /* Both pos and neg, or unknown */
+                _ => Ancestry::Unknown,
+            },
+        };
+        let changed: bool = self.ancestry != Some(merged);
+        self.ancestry = Some(merged);
+        changed
+    }
+
+    pub fn arity(&self) -> Option<usize> {
+        self.column_types
+            .as_ref()
+            .map(|column_types| column_types.len())
+    }
+
+    /// Join the column types of new tuples into mine.
+    /// Returns whether my column types changed.
+    pub fn merge_column_types(&mut self, new_column_types: &[ColumnType]) -> bool {
+        match &mut self.column_types {
+            None => {
+                self.column_types = Some(new_column_types.to_vec());
+                true
+            }
+            Some(column_types) if column_types.len() == new_column_types.len() => {
+                let mut changed: bool = false;
+                for (column_type, new_column_type) in column_types.iter_mut().zip(new_column_types)
+                {
+                    let joined: ColumnType = column_type.join(*new_column_type);
+                    changed |= joined != *column_type;
+                    *column_type = joined;
                 }
+                changed
             }
+            // Nemo rejects programs that use a relation with different arities
+            Some(_) => false,
         }
     }
 }
 
-pub struct ADGFactNode {
-    pub name: String,
+/// An `@import` of a fact node, as far as the ADG needs to know it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportDescriptor {
+    /// The file format, e.g. `csv`
+    pub format: String,
+    /// The file or URL the tuples are read from
+    pub resource: Option<String>,
+    /// The directive as nemo prints it
+    pub directive: String,
+}
+impl ImportDescriptor {
+    pub fn from_import(import: &ImportDirective) -> Self {
+        Self::from_directive(import.to_string())
+    }
+
+    fn from_directive(directive: String) -> Self {
+        // Printed as `@import predicate :- format { attributes } .`
+        let format: String = directive
+            .split_once(":-")
+            .map(|(_, rest)| rest.split('{').next().unwrap_or(rest).trim().to_string())
+            .unwrap_or_default();
+        let resource: Option<String> = import_attribute(&directive, "resource")
+            .and_then(|value| value.strip_prefix('"'))
+            .and_then(|value| value.split_once('"'))
+            .map(|(resource, _)| resource.to_string());
+        ImportDescriptor {
+            format,
+            resource,
+            directive,
+        }
+    }
+}
+impl Display for ImportDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match &self.resource {
+            Some(resource) => f.write_fmt(format_args!("{} import of {}", self.format, resource)),
+            None => f.write_fmt(format_args!("{}", self.directive)),
+        }
+    }
+}
+
+/// Where the tuples of a fact node come from
+#[derive(Clone)]
+pub enum ADGFactNode {
+    /// A fact whose terms are all ground
+    Fact(Vec<GroundTerm>),
+    /// A fact with terms nemo still has to evaluate, e.g. `p(1 + 2)`, as it is printed
+    NonGroundFact(String),
+    Import(ImportDescriptor),
 }
 impl Debug for ADGFactNode {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        f.write_fmt(format_args!("({})", self.name))
+        f.write_fmt(format_args!("({self})"))
+    }
+}
+impl Display for ADGFactNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ADGFactNode::Fact(terms) => {
+                let terms: Vec<String> = terms.iter().map(|gt| gt.to_string()).collect();
+                f.write_fmt(format_args!("({})", terms.join(", ")))
+            }
+            ADGFactNode::NonGroundFact(fact) => f.write_fmt(format_args!("{fact}")),
+            ADGFactNode::Import(import) => f.write_fmt(format_args!("{import}")),
+        }
+    }
+}
+impl ADGFactNode {
+    pub fn from_fact(fact: &Fact) -> Self {
+        match fact_ground_terms(fact) {
+            Some(terms) => ADGFactNode::Fact(terms),
+            None => ADGFactNode::NonGroundFact(fact.to_string()),
+        }
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Sign {
     Positive,
     Negative,
@@ -186,10 +370,89 @@ impl Debug for Sign {
     }
 }
 
+/// A variable a body atom shares with a head atom
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedVariable {
+    pub name: String,
+    /// Argument positions of the variable in the body atom
+    pub body_positions: Vec<usize>,
+    /// Argument positions of the variable in the head atom
+    pub head_positions: Vec<usize>,
+}
+
+/// What the rule of an edge does beyond copying tuples from body to head.
+/// Existential variables and builtins are only recorded,
+/// aggregates make the edge non-monotone.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeFeatures {
+    /// Existential variables of the head atom
+    pub existential_variables: Vec<String>,
+    /// Aggregates of the head atom, e.g. `#min(?distance)`
+    pub aggregates: Vec<String>,
+    /// Arithmetic in the head atom and comparisons in the body of the rule, e.g. `?height >= 50`
+    pub builtins: Vec<String>,
+}
+impl EdgeFeatures {
+    pub fn new(rule: &Rule, head_terms: &[Term]) -> Self {
+        let mut features = EdgeFeatures::default();
+        for term in head_terms {
+            match term {
+                Term::Primitive(Primitive::Variable(variable)) if variable.is_existential() => {
+                    features.existential_variables.push(variable.to_string())
+                }
+                Term::Aggregate(_) => features.aggregates.push(term.to_string()),
+                Term::Operation(_) => features.builtins.push(term.to_string()),
+                _ => (),
+            }
+        }
+        for literal in rule.body() {
+            if let Literal::Operation(operation) = literal {
+                features.builtins.push(operation.to_string());
+            }
+        }
+        features
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.existential_variables.is_empty()
+            && self.aggregates.is_empty()
+            && self.builtins.is_empty()
+    }
+
+    /// Whether more body tuples can mean fewer head tuples,
+    /// so the edge does not pass on ancestry
+    pub fn is_non_monotone(&self) -> bool {
+        !self.aggregates.is_empty()
+    }
+}
+impl Display for EdgeFeatures {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let mut features: Vec<String> = Vec::new();
+        if !self.existential_variables.is_empty() {
+            features.push(format!("exists {}", self.existential_variables.join(", ")));
+        }
+        features.extend(self.aggregates.iter().cloned());
+        features.extend(self.builtins.iter().cloned());
+        f.write_fmt(format_args!("{}", features.join("; ")))
+    }
+}
+
+/// The edge from one body atom of a rule to one of its head atoms.
+/// A rule with a repeated body relation has one edge per body atom.
+#[derive(Clone)]
 pub struct ADGRelationalEdge {
     pub rule_name: Option<String>,
     pub id: ProgramComponentId,
     pub sign: Sign,
+    /// Position of the body atom among the positive or negative body atoms of the rule
+    pub body_index: usize,
+    /// Position of the head atom in the head of the rule
+    pub head_index: usize,
+    pub body_terms: Vec<Term>,
+    pub head_terms: Vec<Term>,
+    /// The variables that occur as arguments of both atoms
+    pub shared_variables: Vec<SharedVariable>,
+    pub features: EdgeFeatures,
 }
 impl Debug for ADGRelationalEdge {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -200,6 +463,95 @@ impl Debug for ADGRelationalEdge {
     }
 }
 
+impl Display for ADGRelationalEdge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let terms = |terms: &[Term]| -> String {
+            terms
+                .iter()
+                .map(|term| term.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        f.write_fmt(format_args!(
+            "{} {:?}: body atom {} ({}) -> head atom {} ({})",
+            self.rule_name.as_deref().unwrap_or("unnamed rule"),
+            self.sign,
+            self.body_index,
+            terms(&self.body_terms),
+            self.head_index,
+            terms(&self.head_terms)
+        ))?;
+        if !self.features.is_empty() {
+            f.write_fmt(format_args!(" [{}]", self.features))?;
+        }
+        Ok(())
+    }
+}
+impl ADGRelationalEdge {
+    /// The edge from the body atom at `body_index` to the head atom at `head_index`
+    pub fn new(
+        rule: &Rule,
+        sign: Sign,
+        body_index: usize,
+        body_atom: &Atom,
+        head_index: usize,
+        head_atom: &Atom,
+    ) -> Self {
+        let body_terms: Vec<Term> = body_atom.terms().cloned().collect();
+        let head_terms: Vec<Term> = head_atom.terms().cloned().collect();
+        let shared_variables: Vec<SharedVariable> = shared_variables(&body_terms, &head_terms);
+        let features: EdgeFeatures = EdgeFeatures::new(rule, &head_terms);
+        Self {
+            rule_name: rule.name(),
+            id: rule.id(),
+            sign,
+            body_index,
+            head_index,
+            body_terms,
+            head_terms,
+            shared_variables,
+            features,
+        }
+    }
+}
+
+/// The name of a term that is a named variable
+fn variable_name(term: &Term) -> Option<&str> {
+    match term {
+        Term::Primitive(Primitive::Variable(variable)) => variable.name(),
+        _ => None,
+    }
+}
+
+/// The variables that are arguments of both term lists, with their positions.
+/// Variables nested in other terms, e.g. in operations, are not considered.
+fn shared_variables(body_terms: &[Term], head_terms: &[Term]) -> Vec<SharedVariable> {
+    let positions = |terms: &[Term], name: &str| -> Vec<usize> {
+        terms
+            .iter()
+            .enumerate()
+            .filter(|(_, term)| variable_name(term) == Some(name))
+            .map(|(position, _)| position)
+            .collect()
+    };
+    let mut shared: Vec<SharedVariable> = Vec::new();
+    for name in body_terms.iter().filter_map(variable_name) {
+        if shared.iter().any(|variable| variable.name == name) {
+            continue;
+        }
+        let head_positions: Vec<usize> = positions(head_terms, name);
+        if !head_positions.is_empty() {
+            shared.push(SharedVariable {
+                name: name.to_string(),
+                body_positions: positions(body_terms, name),
+                head_positions,
+            });
+        }
+    }
+    shared
+}
+
+#[derive(Clone)]
 pub struct ADGFactEdge {}
 impl Debug for ADGFactEdge {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -207,6 +559,7 @@ impl Debug for ADGFactEdge {
     }
 }
 
+#[derive(Clone)]
 pub enum ADGNode {
     ADGRelationalNode(ADGRelationalNode),
     ADGFactNode(ADGFactNode),
@@ -222,6 +575,7 @@ impl Debug for ADGNode {
     }
 }
 
+#[derive(Clone)]
 pub enum ADGEdge {
     ADGRelationalEdge(ADGRelationalEdge),
     ADGFactEdge(ADGFactEdge),
@@ -237,18 +591,95 @@ impl Debug for ADGEdge {
     }
 }
 
+/// The positive and the negative body atoms of a rule
+fn body_atoms_by_sign(rule: &Rule) -> [(Sign, Vec<&Atom>); 2] {
+    [
+        (Sign::Positive, rule.body_positive().collect()),
+        (Sign::Negative, rule.body_negative().collect()),
+    ]
+}
+
+/// A node of the ADG as it is stored in JSON files
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SerializedADGNode {
+    Relational {
+        index: usize,
+        tag: String,
+        inverse_stratum: Option<u32>,
+        ancestry: Option<Ancestry>,
+        column_types: Option<Vec<ColumnType>>,
+    },
+    Fact {
+        index: usize,
+        terms: Vec<SerializedGroundTerm>,
+    },
+    NonGroundFact {
+        index: usize,
+        fact: String,
+    },
+    Import {
+        index: usize,
+        import: ImportDescriptor,
+    },
+}
+
+/// An edge of the ADG as it is stored in JSON files.
+/// Rule id, terms and shared variables are only informative,
+/// they are taken from the rule with the same name when loading.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SerializedADGEdge {
+    Relational {
+        source: usize,
+        target: usize,
+        sign: Sign,
+        rule_name: Option<String>,
+        rule_id: String,
+        body_index: usize,
+        head_index: usize,
+        body_terms: Vec<String>,
+        head_terms: Vec<String>,
+        shared_variables: Vec<SharedVariable>,
+        #[serde(default)]
+        features: EdgeFeatures,
+    },
+    Fact {
+        source: usize,
+        target: usize,
+    },
+}
+
+/// The ADG as it is stored in JSON files.
+/// Nodes and edges are listed in the order of their indices.
+#[derive(Serialize, Deserialize)]
+struct SerializedADG {
+    output_predicate: Option<String>,
+    ground_terms: Vec<SerializedGroundTerm>,
+    nodes: Vec<SerializedADGNode>,
+    edges: Vec<SerializedADGEdge>,
+}
+
+#[derive(Clone, Default)]
 pub struct AnnotatedDependencyGraph {
     graph: Graph<ADGNode, ADGEdge, Directed, u32>,
     predicates: Vec<Tag>,
     predicate_ids: HashMap<Tag, NodeIndex>,
     output_predicate: Option<Tag>,
     ground_terms: Vec<GroundTerm>,
+    /// The transformations applied since the ADG was built, in order
+    applied_steps: Vec<TransformationStep>,
+    /// The rows of imported files by resource, only if they were loaded
+    imported_data: BTreeMap<String, ImportedData>,
+    /// Errors transformations ran into while updating the ADG, not taken yet
+    failed_updates: Vec<String>,
 }
 
-// TODO: Multi-edges wichtig!
 impl<'a> AnnotatedDependencyGraph {
-    pub fn from_program(program: &ProgramHandle) -> Option<Self> {
-        let predicates = program.all_predicates().into_iter().collect::<Vec<Tag>>();
+    pub fn from_program(program: &ProgramHandle) -> Result<Self, MetamorphicError> {
+        let mut predicates = program.all_predicates().into_iter().collect::<Vec<Tag>>();
+        // Sort them, so the node indices only depend on the program
+        predicates.sort_by(|a, b| a.name().cmp(b.name()));
 
         // Find ground terms, which might be the same as constant symbols
         // TODO: check this
@@ -271,66 +702,24 @@ impl<'a> AnnotatedDependencyGraph {
             predicate_ids: HashMap::new(),
             output_predicate: None,
             ground_terms,
+            applied_steps: Vec::new(),
+            imported_data: BTreeMap::new(),
+            failed_updates: Vec::new(),
         };
         //println!("{:#?}", adg.predicates);
         adg.init_rel_nodes();
         for statement in program.statements() {
             match statement {
                 statement::Statement::Fact(fact) => {
-                    //println!("{fact:?}");
-                    let mut fact_str: String = String::new();
-                    for term in fact.primitive_terms() {
-                        let mut term_str = term.to_string();
-                        term_str.push_str(",\n");
-                        fact_str.push_str(&term_str);
-                    }
-                    let fact_node: NodeIndex = adg.add_fact_node(fact_str);
-                    let rel_node: NodeIndex = adg.get_rel_node_tag(fact.predicate());
+                    let fact_node: NodeIndex = adg.add_fact_node(ADGFactNode::from_fact(fact));
+                    let rel_node: NodeIndex = adg.get_rel_node_tag(fact.predicate())?;
                     adg.add_fact_edge(fact_node, rel_node);
                 }
-                statement::Statement::Rule(rule) => {
-                    //todo!("Store variables");
-                    for (_ii, pos_atom) in rule.body_positive().enumerate() {
-                        let start_node = adg.get_rel_node_tag(&pos_atom.predicate());
-                        for head_atom in rule.head() {
-                            let end_node = adg.get_rel_node_tag(&head_atom.predicate());
-                            //println!("rule name:{:?}", rule.name());
-                            adg.add_rel_edge(
-                                rule.name(),
-                                Sign::Positive,
-                                start_node,
-                                end_node,
-                                rule.id(),
-                            );
-                        }
-                    }
-                    //todo!("Store variables");
-                    for (_ii, neg_atom) in rule.body_negative().enumerate() {
-                        let start_node = adg.get_rel_node_tag(&neg_atom.predicate());
-                        for head_atom in rule.head() {
-                            let end_node = adg.get_rel_node_tag(&head_atom.predicate());
-                            adg.add_rel_edge(
-                                rule.name(),
-                                Sign::Negative,
-                                start_node,
-                                end_node,
-                                rule.id(),
-                            );
-                        }
-                    }
-                }
+                statement::Statement::Rule(rule) => adg.add_rule_edges(rule)?,
                 statement::Statement::Import(import) => {
-                    //println!("{import:?}");
-                    let mut import_str: String = String::new();
-                    //for term in import.primitive_terms() {
-                    //import.origin()
-                    //import_str.push_str(&term.to_string());
-                    //}
-                    // I think the first is the file name
-                    import_str
-                        .push_str(&(import.primitive_terms().collect::<Vec<_>>()[0].to_string()));
-                    let fact_node: NodeIndex = adg.add_fact_node(import_str);
-                    let rel_node: NodeIndex = adg.get_rel_node_tag(import.predicate());
+                    let fact_node: NodeIndex = adg
+                        .add_fact_node(ADGFactNode::Import(ImportDescriptor::from_import(import)));
+                    let rel_node: NodeIndex = adg.get_rel_node_tag(import.predicate())?;
                     adg.add_fact_edge(fact_node, rel_node);
                 }
                 statement::Statement::Export(export) => {}
@@ -338,18 +727,244 @@ impl<'a> AnnotatedDependencyGraph {
                 statement::Statement::Parameter(parameter) => {}
             }
         }
+        adg.infer_column_types(program)?;
 
-        Some(adg)
+        Ok(adg)
     }
 
-    pub fn write_self_to_file(&self, path: Option<String>, name: Option<String>) {
-        let basic_dot = Dot::new(&self.graph);
+    /// Write the ADG as a graphviz `.dot` file and a Mermaid `.mmd` file
+    pub fn write_self_to_file(
+        &self,
+        path: Option<String>,
+        name: Option<String>,
+    ) -> Result<(), MetamorphicError> {
         let mut path = path.unwrap_or(String::from(""));
         path.push_str("/");
         path.push_str(name.unwrap_or(String::from("adg")).as_str());
-        path.push_str(".dot");
-        std::fs::write(path, format!("{:?}", basic_dot)).unwrap();
+        for (file, content) in [
+            (format!("{path}.dot"), self.to_dot()),
+            (format!("{path}.mmd"), self.to_mermaid()),
+        ] {
+            if std::fs::write(&file, content).is_err() {
+                return Err(MetamorphicError::Write(PathBuf::from(file)));
+            }
+        }
+        Ok(())
+    }
+    /// Serialize the ADG to pretty printed JSON.
+    /// The applied steps and the imported data are not serialized.
+    pub fn to_json(&self) -> Result<String, MetamorphicError> {
+        let nodes: Vec<SerializedADGNode> = self
+            .graph
+            .node_indices()
+            .map(|node| match &self.graph[node] {
+                ADGNode::ADGRelationalNode(rel_node) => SerializedADGNode::Relational {
+                    index: node.index(),
+                    tag: rel_node.tag.name().to_string(),
+                    inverse_stratum: rel_node.inverse_stratum,
+                    ancestry: rel_node.ancestry,
+                    column_types: rel_node.column_types.clone(),
+                },
+                ADGNode::ADGFactNode(ADGFactNode::Fact(terms)) => SerializedADGNode::Fact {
+                    index: node.index(),
+                    terms: terms.iter().map(SerializedGroundTerm::new).collect(),
+                },
+                ADGNode::ADGFactNode(ADGFactNode::NonGroundFact(fact)) => {
+                    SerializedADGNode::NonGroundFact {
+                        index: node.index(),
+                        fact: fact.clone(),
+                    }
+                }
+                ADGNode::ADGFactNode(ADGFactNode::Import(import)) => SerializedADGNode::Import {
+                    index: node.index(),
+                    import: import.clone(),
+                },
+            })
+            .collect();
+        let edges: Vec<SerializedADGEdge> = self
+            .graph
+            .edge_references()
+            .map(|edge| match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge) => SerializedADGEdge::Relational {
+                    source: edge.source().index(),
+                    target: edge.target().index(),
+                    sign: rel_edge.sign.clone(),
+                    rule_name: rel_edge.rule_name.clone(),
+                    rule_id: rel_edge.id.to_string(),
+                    body_index: rel_edge.body_index,
+                    head_index: rel_edge.head_index,
+                    body_terms: rel_edge.body_terms.iter().map(|t| t.to_string()).collect(),
+                    head_terms: rel_edge.head_terms.iter().map(|t| t.to_string()).collect(),
+                    shared_variables: rel_edge.shared_variables.clone(),
+                    features: rel_edge.features.clone(),
+                },
+                ADGEdge::ADGFactEdge(_) => SerializedADGEdge::Fact {
+                    source: edge.source().index(),
+                    target: edge.target().index(),
+                },
+            })
+            .collect();
+        let serialized = SerializedADG {
+            output_predicate: self
+                .output_predicate
+                .as_ref()
+                .map(|tag| tag.name().to_string()),
+            ground_terms: self
+                .ground_terms
+                .iter()
+                .map(SerializedGroundTerm::new)
+                .collect(),
+            nodes,
+            edges,
+        };
+        match serde_json::to_string_pretty(&serialized) {
+            Ok(json) => Ok(json),
+            Err(error) => Err(MetamorphicError::AdgJson(format!(
+                "Failed to serialize ADG: {error}"
+            ))),
+        }
+    }
+
+    /// Rebuild an ADG from its JSON form.
+    /// Rule ids are only valid within one program, so the `rule_id` stored in the JSON
+    /// is ignored: the edges get the ids and atoms of the rules with the same name in `program`.
+    /// Imported data is not part of the JSON, load it again with `load_imports` if needed.
+    pub fn from_json(json: &str, program: &ProgramHandle) -> Result<Self, MetamorphicError> {
+        let serialized: SerializedADG = match serde_json::from_str(json) {
+            Ok(serialized) => serialized,
+            Err(error) => {
+                return Err(MetamorphicError::AdgJson(format!("Invalid ADG: {error}")));
+            }
+        };
+        let rules: HashMap<String, &Rule> = program
+            .rules()
+            .filter_map(|rule| rule.name().map(|name| (name.to_string(), rule)))
+            .collect();
+
+        let mut ground_terms: Vec<GroundTerm> = Vec::new();
+        for gt in &serialized.ground_terms {
+            ground_terms.push(gt.to_ground_term().map_err(MetamorphicError::AdgJson)?);
+        }
+        let mut adg: AnnotatedDependencyGraph = AnnotatedDependencyGraph {
+            graph: Graph::default(),
+            predicates: Vec::new(),
+            predicate_ids: HashMap::new(),
+            output_predicate: serialized.output_predicate.map(Tag::new),
+            ground_terms,
+            applied_steps: Vec::new(),
+            imported_data: BTreeMap::new(),
+            failed_updates: Vec::new(),
+        };
+
+        // Nodes are added in the order of their indices, so the indices stay the same
+        for serialized_node in serialized.nodes {
+            let (index, node) = match serialized_node {
+                SerializedADGNode::Relational {
+                    index,
+                    tag,
+                    inverse_stratum,
+                    ancestry,
+                    column_types,
+                } => {
+                    let node = adg.add_rel_node(&Tag::new(tag));
+                    if let ADGNode::ADGRelationalNode(rel_node) = &mut adg.graph[node] {
+                        rel_node.inverse_stratum = inverse_stratum;
+                        rel_node.ancestry = ancestry;
+                        rel_node.column_types = column_types;
+                    }
+                    (index, node)
+                }
+                SerializedADGNode::Fact { index, terms } => {
+                    let terms: Vec<GroundTerm> = terms
+                        .iter()
+                        .map(|gt| gt.to_ground_term().map_err(MetamorphicError::AdgJson))
+                        .collect::<Result<Vec<GroundTerm>, MetamorphicError>>()?;
+                    (index, adg.add_fact_node(ADGFactNode::Fact(terms)))
+                }
+                SerializedADGNode::NonGroundFact { index, fact } => {
+                    (index, adg.add_fact_node(ADGFactNode::NonGroundFact(fact)))
+                }
+                SerializedADGNode::Import { index, import } => {
+                    (index, adg.add_fact_node(ADGFactNode::Import(import)))
+                }
+            };
+            if node.index() != index {
+                return Err(MetamorphicError::AdgJson(format!(
+                    "Node {index} is listed at position {}",
+                    node.index()
+                )));
+            }
+        }
+
+        let num_nodes: usize = adg.graph.node_count();
+        for serialized_edge in serialized.edges {
+            let (source, target) = match &serialized_edge {
+                SerializedADGEdge::Relational { source, target, .. }
+                | SerializedADGEdge::Fact { source, target } => (*source, *target),
+            };
+            if source >= num_nodes || target >= num_nodes {
+                return Err(MetamorphicError::AdgJson(format!(
+                    "Edge {source} -> {target} refers to a node that does not exist"
+                )));
+            }
+            let (source, target) = (NodeIndex::new(source), NodeIndex::new(target));
+            match serialized_edge {
+                SerializedADGEdge::Relational {
+                    sign,
+                    rule_name,
+                    body_index,
+                    head_index,
+                    ..
+                } => {
+                    let edge_description = format!(
+                        "Edge {} -> {} of rule {}",
+                        source.index(),
+                        target.index(),
+                        rule_name.as_deref().unwrap_or("without name")
+                    );
+                    let Some(rule) = rule_name.as_ref().and_then(|name| rules.get(name)) else {
+                        return Err(MetamorphicError::AdgJson(format!(
+                            "{edge_description}: the rule is not part of the program"
+                        )));
+                    };
+                    let body_atom: Option<&Atom> = body_atoms_by_sign(rule)
+                        .into_iter()
+                        .find(|(atoms_sign, _)| *atoms_sign == sign)
+                        .and_then(|(_, atoms)| atoms.get(body_index).copied());
+                    let (Some(body_atom), Some(head_atom)) =
+                        (body_atom, rule.head().get(head_index))
+                    else {
+                        return Err(MetamorphicError::AdgJson(format!(
+                            "{edge_description}: the rule has no body atom {body_index} or head atom {head_index}"
+                        )));
+                    };
+                    let rel_edge = ADGRelationalEdge::new(
+                        rule, sign, body_index, body_atom, head_index, head_atom,
+                    );
+                    adg.add_rel_edge(source, target, rel_edge);
+                }
+                SerializedADGEdge::Fact { .. } => adg.add_fact_edge(source, target),
+            }
+        }
+        Ok(adg)
+    }
+
+    /// Write the ADG as JSON to the given file
+    pub fn write_json_to_file(&self, path: &Path) -> Result<(), MetamorphicError> {
+        match std::fs::write(path, self.to_json()?) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(MetamorphicError::Write(path.to_path_buf())),
+        }
+    }
+
+    /// Read an ADG from a JSON file, see `from_json`
+    pub fn load_json(path: &Path, program: &ProgramHandle) -> Result<Self, MetamorphicError> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::from_json(&json, program),
+            Err(_) => Err(MetamorphicError::ReadFile(path.to_path_buf())),
+        }
     }
+
     fn init_rel_nodes(&mut self) {
         for tag in self.predicates.clone() {
             self.add_rel_node(&tag);
@@ -364,183 +979,331 @@ impl<'a> AnnotatedDependencyGraph {
         self.output_predicate = Some(tag.clone());
     }
 
-    pub fn calculate_ancestry_and_inverse_stratum(&mut self) {
+    /// Get the output predicate, if one was chosen already
+    pub fn get_output_predicate(&self) -> Option<&Tag> {
+        self.output_predicate.as_ref()
+    }
+
+    /// Compute ancestry and inverse stratum of all relational nodes from scratch,
+    /// starting at the output predicate. Relations the output does not depend on
+    /// get `Ancestry::None` and no inverse stratum.
+    pub fn calculate_ancestry_and_inverse_stratum(&mut self) -> Result<(), MetamorphicError> {
         // Note: We use inverse stratum!
-        match &self.output_predicate {
-            None => {
-                println!("No output predicate set!");
-                exit(1);
-            }
-            Some(output_predicate) => {
-                println!(
-                    "Beginning inverse_stratum and ancestry computation starting at node {}",
-                    output_predicate.name()
-                );
-                // We kinda should know, that the program is stratifiable, as otherwise
-                // Nemo couldn't parse it, right???
-                let output_node = self.get_rel_node_tag(output_predicate);
-                self.set_ancestry_inverse_stratum(output_node, 0, Ancestry::Positive);
+        let output_predicate: Tag = match &self.output_predicate {
+            None => return Err(MetamorphicError::NoOutputPredicate),
+            Some(output_predicate) => output_predicate.clone(),
+        };
+        println!(
+            "Beginning inverse_stratum and ancestry computation starting at node {}",
+            output_predicate.name()
+        );
+        let output_node: NodeIndex = self.get_rel_node_tag(&output_predicate)?;
+        // Inverse strata only exist for stratifiable programs
+        if let Some(cycle) = self.find_negative_cycle() {
+            return Err(MetamorphicError::NegativeCycle(cycle));
+        }
+        for node in self.graph.node_weights_mut() {
+            if let ADGNode::ADGRelationalNode(rel_node) = node {
+                rel_node.ancestry = None;
+                rel_node.inverse_stratum = None;
             }
         }
+        self.propagate_ancestry_inverse_stratum(VecDeque::from([(
+            output_node,
+            0,
+            Ancestry::Positive,
+        )]))?;
         println!("Ancestry and Inverse Stratum computation complete.");
+        Ok(())
     }
 
-    fn set_ancestry_inverse_stratum(
+    /// Update ancestry and inverse stratum after edges into `node` were added.
+    /// Adding edges only raises the annotations, so it suffices to
+    /// push the annotation of `node` to its (new) predecessors.
+    pub fn update_ancestry_and_inverse_stratum(
         &mut self,
         node: NodeIndex,
+    ) -> Result<(), MetamorphicError> {
+        let mut worklist: VecDeque<(NodeIndex, u32, Ancestry)> = VecDeque::new();
+        if let (Some(ancestry), Some(inverse_stratum)) = self.get_annotation(node)? {
+            self.plan_predecessors(node, inverse_stratum, ancestry, &mut worklist);
+        }
+        self.propagate_ancestry_inverse_stratum(worklist)
+    }
+
+    /// Ancestry and inverse stratum of a relational node
+    fn get_annotation(
+        &self,
+        node: NodeIndex,
+    ) -> Result<(Option<Ancestry>, Option<u32>), MetamorphicError> {
+        match self.graph.node_weight(node) {
+            None => Err(MetamorphicError::UnknownNode(node.index())),
+            Some(ADGNode::ADGFactNode(_)) => {
+                Err(MetamorphicError::ExpectedRelationalNode(node.index()))
+            }
+            Some(ADGNode::ADGRelationalNode(rel_node)) => {
+                Ok((rel_node.ancestry, rel_node.inverse_stratum))
+            }
+        }
+    }
+
+    /// Add the annotations the predecessors of `node` get from it to the worklist
+    fn plan_predecessors(
+        &self,
+        node: NodeIndex,
         inverse_stratum: u32,
         ancestry: Ancestry,
+        worklist: &mut VecDeque<(NodeIndex, u32, Ancestry)>,
     ) {
-        //println!("Call A_I_S for node {}", node.index());
-        let mut_node: Option<&mut ADGNode> = self.graph.node_weight_mut(node);
-        match mut_node {
-            None => {
-                println!(
-                    "Attempted to set ancestry and inverse_stratum for non-existant node: {}",
-                    node.index()
-                );
-                exit(1);
+        for edge in self
+            .graph
+            .edges_directed(node, petgraph::Direction::Incoming)
+        {
+            match edge.weight() {
+                ADGEdge::ADGFactEdge(_) => (), // Done
+                // The head of an aggregate can lose tuples when the body gains some
+                ADGEdge::ADGRelationalEdge(relational_edge)
+                    if relational_edge.features.is_non_monotone() =>
+                {
+                    let inverse_stratum: u32 = match relational_edge.sign {
+                        Sign::Negative => inverse_stratum + 1,
+                        Sign::Positive => inverse_stratum,
+                    };
+                    worklist.push_back((edge.source(), inverse_stratum, Ancestry::Unknown));
+                }
+                ADGEdge::ADGRelationalEdge(relational_edge) => match relational_edge.sign {
+                    Sign::Negative => {
+                        worklist.push_back((
+                            edge.source(),
+                            inverse_stratum + 1,
+                            ancestry.inverse(),
+                        ));
+                    }
+                    Sign::Positive => {
+                        worklist.push_back((edge.source(), inverse_stratum, ancestry));
+                    }
+                },
             }
-            Some(adg_node) => match adg_node {
-                ADGNode::ADGFactNode(_) => {
-                    println!(
-                        "Attempted to set ancestry and inverse_stratum for a fact node: {}",
-                        node.index()
-                    );
-                    exit(1);
-                }
-                ADGNode::ADGRelationalNode(adg_node) => {
-                    adg_node.merge(ancestry);
-                    match adg_node.inverse_stratum {
-                        None => {
-                            adg_node.inverse_stratum = Some(inverse_stratum);
-                            let mut plan_recursive_call: Vec<(NodeIndex, u32, Ancestry)> =
-                                Vec::new();
-                            /* println!(
-                                "{:#?}",
-                                self.graph_mut()
-                                    .edges_directed(node, petgraph::Direction::Incoming)
-                                    .collect::<Vec<_>>()
-                            ); */
-                            for edge in self
-                                .graph_mut()
-                                .edges_directed(node, petgraph::Direction::Incoming)
-                            {
-                                // let to_node: NodeIndex = edge.target();
-                                //let edge: &ADGEdge = edge.weight();
-                                let edge_weight: &ADGEdge = edge.weight();
-                                //let adg_edge = self.graph.edge_weight_mut(edge);
-                                match edge_weight {
-                                    ADGEdge::ADGFactEdge(_) => (), // Done
-                                    ADGEdge::ADGRelationalEdge(relational_edge) => {
-                                        match relational_edge.sign {
-                                            Sign::Negative => {
-                                                plan_recursive_call.push((
-                                                    edge.source(),
-                                                    inverse_stratum + 1,
-                                                    ancestry.inverse(),
-                                                ));
-                                            }
-                                            Sign::Positive => {
-                                                plan_recursive_call.push((
-                                                    edge.source(),
-                                                    inverse_stratum,
-                                                    ancestry,
-                                                ));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            //println!("Recursive call for neighbours: {:#?}", plan_recursive_call);
-                            for (n, is, a) in plan_recursive_call {
-                                self.set_ancestry_inverse_stratum(n, is, a);
-                            }
-                        }
-                        Some(old_inverse_stratum) => {
-                            if old_inverse_stratum < inverse_stratum {
-                                // Some new relation tells us that we need to
-                                // set the inverse_stratum one higher!
-                                adg_node.inverse_stratum = Some(inverse_stratum);
-                                let mut plan_recursive_call: Vec<(NodeIndex, u32, Ancestry)> =
-                                    Vec::new();
-                                /* println!(
-                                    "{:#?}",
-                                    self.graph_mut()
-                                        .edges_directed(node, petgraph::Direction::Incoming)
-                                        .collect::<Vec<_>>()
-                                ); */
-                                for edge in self
-                                    .graph_mut()
-                                    .edges_directed(node, petgraph::Direction::Incoming)
-                                {
-                                    // let to_node: NodeIndex = edge.target();
-                                    //let edge: &ADGEdge = edge.weight();
-                                    let edge_weight: &ADGEdge = edge.weight();
-                                    //let adg_edge = self.graph.edge_weight_mut(edge);
-                                    match edge_weight {
-                                        ADGEdge::ADGFactEdge(_) => (), // Done
-                                        ADGEdge::ADGRelationalEdge(relational_edge) => {
-                                            match relational_edge.sign {
-                                                Sign::Negative => {
-                                                    plan_recursive_call.push((
-                                                        edge.source(),
-                                                        inverse_stratum + 1,
-                                                        ancestry.inverse(),
-                                                    ));
-                                                }
-                                                Sign::Positive => {
-                                                    plan_recursive_call.push((
-                                                        edge.source(),
-                                                        inverse_stratum,
-                                                        ancestry,
-                                                    ));
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                                /* println!(
-                                    "Recursive call for neighbours: {:#?}",
-                                    plan_recursive_call
-                                ); */
-                                for (n, is, a) in plan_recursive_call {
-                                    self.set_ancestry_inverse_stratum(n, is, a);
-                                }
-                                // do backwards neighbours again!
-                            } else if old_inverse_stratum == inverse_stratum {
-                                // This node already has a inverse_stratum, and thus
-                                // we can assume its neighbours already have
-                                // the correct inverse_stratum
-                                //println!("Done 1");
-                            } else
-                            /* old inverse_stratum > inverse_stratum */
-                            {
-                                // This node already has a inverse_stratum, and thus
-                                // we can assume its neighbours already have
-                                // the correct inverse_stratum
-                                //println!("Done 2");
-                            }
+        }
+    }
+
+    /// Worklist fixpoint: merge the ancestry into each node, raise its inverse stratum
+    /// and revisit its predecessors whenever one of them changed.
+    /// Afterwards, relational nodes that were never reached get `Ancestry::None`.
+    fn propagate_ancestry_inverse_stratum(
+        &mut self,
+        mut worklist: VecDeque<(NodeIndex, u32, Ancestry)>,
+    ) -> Result<(), MetamorphicError> {
+        // On a stratifiable program no inverse stratum exceeds the number of relations
+        let max_inverse_stratum: u32 = self.predicate_ids.len() as u32;
+        while let Some((node, inverse_stratum, ancestry)) = worklist.pop_front() {
+            let (ancestry, inverse_stratum) = match self.graph.node_weight_mut(node) {
+                None => return Err(MetamorphicError::UnknownNode(node.index())),
+                Some(ADGNode::ADGFactNode(_)) => {
+                    return Err(MetamorphicError::ExpectedRelationalNode(node.index()));
+                }
+                Some(ADGNode::ADGRelationalNode(adg_node)) => {
+                    let ancestry_changed: bool = adg_node.merge(ancestry);
+                    let stratum_changed: bool = match adg_node.inverse_stratum {
+                        Some(old_inverse_stratum) => old_inverse_stratum < inverse_stratum,
+                        None => true,
+                    };
+                    if stratum_changed {
+                        // Some new relation tells us that we need to
+                        // set the inverse_stratum higher!
+                        if inverse_stratum > max_inverse_stratum {
+                            let cycle: DependencyCycle = self
+                                .find_negative_cycle()
+                                .unwrap_or(DependencyCycle { edges: Vec::new() });
+                            return Err(MetamorphicError::NegativeCycle(cycle));
                         }
+                        adg_node.inverse_stratum = Some(inverse_stratum);
                     }
+                    if !ancestry_changed && !stratum_changed {
+                        // We can assume its neighbours already have
+                        // the correct ancestry and inverse_stratum
+                        continue;
+                    }
+                    (
+                        adg_node.ancestry.unwrap_or(Ancestry::None),
+                        adg_node.inverse_stratum.unwrap_or(inverse_stratum),
+                    )
                 }
-            },
+            };
+            self.plan_predecessors(node, inverse_stratum, ancestry, &mut worklist);
         }
-        //self.graph.update_edge(a, b, weight)
+
+        for node in self.graph.node_weights_mut() {
+            if let ADGNode::ADGRelationalNode(rel_node) = node {
+                if rel_node.ancestry.is_none() {
+                    rel_node.ancestry = Some(Ancestry::None);
+                }
+            }
+        }
+        Ok(())
     }
 
     // Add a new relational node with this tag. Register the relational name.
-    pub fn add_rel_node(&mut self, tag: &Tag) {
+    pub fn add_rel_node(&mut self, tag: &Tag) -> NodeIndex {
         self.predicates.push(tag.clone());
-        self.predicate_ids.insert(
-            tag.clone(),
-            self.graph
-                .add_node(ADGNode::ADGRelationalNode(ADGRelationalNode {
-                    tag: tag.clone(),
-                    inverse_stratum: None,
-                    ancestry: None,
-                })),
-        );
+        let node: NodeIndex = self
+            .graph
+            .add_node(ADGNode::ADGRelationalNode(ADGRelationalNode {
+                tag: tag.clone(),
+                inverse_stratum: None,
+                ancestry: None,
+                column_types: None,
+            }));
+        self.predicate_ids.insert(tag.clone(), node);
+        node
+    }
+
+    /// Infer arity and column types of the relational nodes from the program:
+    /// from the arities nemo computed, the formats of imports, the values of facts and
+    /// by propagating the column types of rule bodies to rule heads.
+    /// Known column types are only ever generalized.
+    pub fn infer_column_types(&mut self, program: &ProgramHandle) -> Result<(), MetamorphicError> {
+        for (tag, arity) in program.arities() {
+            self.merge_column_types(&tag, &vec![ColumnType::Unknown; arity])?;
+        }
+        for import in program.imports() {
+            if let Some(column_types) = import_column_types(&import.to_string()) {
+                self.merge_column_types(import.predicate(), &column_types)?;
+            }
+        }
+        for fact in program.facts() {
+            let column_types: Vec<ColumnType> = fact
+                .terms()
+                .map(|term| match term {
+                    Term::Primitive(Primitive::Ground(gt)) => ColumnType::of_ground_term(gt),
+                    _ => ColumnType::Any,
+                })
+                .collect();
+            self.merge_column_types(fact.predicate(), &column_types)?;
+        }
+
+        // The column types only grow, so this terminates
+        let mut changed: bool = true;
+        while changed {
+            changed = false;
+            for rule in program.rules() {
+                changed |= self.merge_head_column_types(rule)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Propagate the column types of the rule's body to its head relations.
+    /// Returns whether the column types of a head relation changed.
+    pub fn merge_head_column_types(&mut self, rule: &Rule) -> Result<bool, MetamorphicError> {
+        let mut changed: bool = false;
+        for head_atom in rule.head() {
+            let mut column_types: Vec<ColumnType> = Vec::new();
+            for term in head_atom.terms() {
+                column_types.push(self.column_type_of_head_term(rule, term)?);
+            }
+            changed |= self.merge_column_types(&head_atom.predicate(), &column_types)?;
+        }
+        Ok(changed)
+    }
+
+    /// The type of the values a head term of the rule takes
+    fn column_type_of_head_term(
+        &self,
+        rule: &Rule,
+        term: &Term,
+    ) -> Result<ColumnType, MetamorphicError> {
+        let name: &str = match term {
+            Term::Primitive(Primitive::Ground(gt)) => return Ok(ColumnType::of_ground_term(gt)),
+            Term::Primitive(Primitive::Variable(variable)) => match variable.name() {
+                Some(name) => name,
+                None => return Ok(ColumnType::Any),
+            },
+            // Results of operations and aggregates
+            _ => return Ok(ColumnType::Any),
+        };
+        let mut column_type: Option<ColumnType> = None;
+        for body_atom in rule.body_positive() {
+            for (position, body_term) in body_atom.terms().enumerate() {
+                if variable_name(body_term) == Some(name) {
+                    let body_column_type: ColumnType =
+                        self.get_column_type(&body_atom.predicate(), position)?;
+                    column_type = Some(match column_type {
+                        None => body_column_type,
+                        Some(column_type) => column_type.join(body_column_type),
+                    });
+                }
+            }
+        }
+        // Existential variables and variables bound by operations
+        Ok(column_type.unwrap_or(ColumnType::Any))
+    }
+
+    /// Join the column types of new tuples into the relation's column types.
+    /// Returns whether they changed.
+    pub fn merge_column_types(
+        &mut self,
+        tag: &Tag,
+        column_types: &[ColumnType],
+    ) -> Result<bool, MetamorphicError> {
+        let node: NodeIndex = self.get_rel_node_tag(tag)?;
+        match &mut self.graph[node] {
+            ADGNode::ADGRelationalNode(rel_node) => Ok(rel_node.merge_column_types(column_types)),
+            ADGNode::ADGFactNode(_) => Err(MetamorphicError::ExpectedRelationalNode(node.index())),
+        }
+    }
+
+    /// The column types of a relation, None while its arity is unknown
+    pub fn get_column_types(&self, tag: &Tag) -> Result<Option<Vec<ColumnType>>, MetamorphicError> {
+        Ok(self.get_rel_node(tag)?.column_types.clone())
+    }
+
+    /// The type of one column of a relation, Unknown if the relation has no such column (yet)
+    fn get_column_type(&self, tag: &Tag, position: usize) -> Result<ColumnType, MetamorphicError> {
+        Ok(self
+            .get_rel_node(tag)?
+            .column_types
+            .as_ref()
+            .and_then(|column_types| column_types.get(position).copied())
+            .unwrap_or(ColumnType::Unknown))
+    }
+
+    /// Whether there is a relational node with this tag
+    pub fn has_rel_node(&self, tag: &Tag) -> bool {
+        self.predicate_ids.contains_key(tag)
+    }
+
+    /// Record a transformation that was applied to the program
+    pub fn record_step(&mut self, step: TransformationStep) {
+        self.applied_steps.push(step);
+    }
+
+    /// Get the transformations applied since the ADG was built, in order
+    pub fn get_applied_steps(&self) -> &Vec<TransformationStep> {
+        &self.applied_steps
+    }
+
+    /// Record an error a transformation ran into while updating the ADG.
+    /// `apply` can only return errors of nemo, so the sequence takes it afterwards.
+    pub fn record_failed_update(&mut self, message: String) {
+        println!("{message}");
+        self.failed_updates.push(message);
+    }
+
+    /// Take the errors recorded since they were last taken
+    pub fn take_failed_updates(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.failed_updates)
+    }
+
+    /// Register a ground term, if it is not registered yet.
+    pub fn register_ground_term(&mut self, ground_term: &GroundTerm) {
+        if self
+            .ground_terms
+            .iter()
+            .all(|gt| ground_term.value() != gt.value())
+        {
+            self.ground_terms.push(ground_term.clone());
+        }
     }
 
     /// Get the ground terms that appear in the program.
@@ -588,6 +1351,19 @@ impl<'a> AnnotatedDependencyGraph {
         new_constant
     }
 
+    /// Get and register a new constant for a column of the given type.
+    /// There are no new doubles and floats, their columns get integers.
+    pub fn get_and_register_new_constant(
+        &'a mut self,
+        column_type: ColumnType,
+        rng: &'a mut ChaCha8Rng,
+    ) -> GroundTerm {
+        match column_type {
+            ColumnType::String => self.get_and_register_new_string_constant(rng),
+            _ => self.get_and_register_new_integer_constant(rng),
+        }
+    }
+
     /// Get a new relation name. Does not register the relation name in the adg.
     pub fn get_new_relation_name(&'a mut self, rng: &'a mut ChaCha8Rng) -> String {
         
@@ -608,28 +1384,32 @@ impl<'a> AnnotatedDependencyGraph {
     }
 
     /// Get a predicates `nodeIndex` based on its tag (= name)
-    pub fn get_rel_node_tag(&self, tag: &Tag) -> NodeIndex {
-        self.predicate_ids[tag]
+    pub fn get_rel_node_tag(&self, tag: &Tag) -> Result<NodeIndex, MetamorphicError> {
+        match self.predicate_ids.get(tag) {
+            None => Err(MetamorphicError::UnknownRelation(tag.clone())),
+            Some(node) => Ok(*node),
+        }
     }
 
     /// Get an iterator over a nodes edges, outgoing or incoming based on `dir` parameter
-    pub fn get_node_edges(&self, tag: &Tag, dir: petgraph::Direction) -> Edges<ADGEdge, Directed> {
-        self.graph.edges_directed(self.get_rel_node_tag(tag), dir)
+    pub fn get_node_edges(
+        &self,
+        tag: &Tag,
+        dir: petgraph::Direction,
+    ) -> Result<Edges<'_, ADGEdge, Directed>, MetamorphicError> {
+        Ok(self.graph.edges_directed(self.get_rel_node_tag(tag)?, dir))
     }
 
     /// Get a relation node based on its `tag` (= name)
-    pub fn get_rel_node(&self, tag: &Tag) -> &ADGRelationalNode {
-        match self.graph.node_weight(self.predicate_ids[tag]) {
-            None => {
-                println!("Could not find node {}", tag);
-                exit(1);
-            }
+    pub fn get_rel_node(&self, tag: &Tag) -> Result<&ADGRelationalNode, MetamorphicError> {
+        let node: NodeIndex = self.get_rel_node_tag(tag)?;
+        match self.graph.node_weight(node) {
+            None => Err(MetamorphicError::UnknownRelation(tag.clone())),
             Some(weight) => match weight {
-                ADGNode::ADGFactNode(fact) => {
-                    println!("Expected relation node for {} but found fact node", tag);
-                    exit(1);
+                ADGNode::ADGFactNode(_) => {
+                    Err(MetamorphicError::ExpectedRelationalNode(node.index()))
                 }
-                ADGNode::ADGRelationalNode(rel) => rel,
+                ADGNode::ADGRelationalNode(rel) => Ok(rel),
             },
         }
     }
@@ -643,28 +1423,174 @@ impl<'a> AnnotatedDependencyGraph {
 
     pub fn add_rel_edge(
         &mut self,
-        rule_name: Option<String>,
-        sign: Sign,
         start_node: NodeIndex,
         end_node: NodeIndex,
-        rule_id: ProgramComponentId,
+        rel_edge: ADGRelationalEdge,
     ) -> EdgeIndex {
-        //let start_node : &ADGNode = Self::get_rel_node_node_index(graph, start_node.into());
-        //let end_node : &ADGNode = Self::get_rel_node_node_index(graph, end_node.into());
-        self.graph.add_edge(
-            start_node,
-            end_node,
-            ADGEdge::ADGRelationalEdge(ADGRelationalEdge {
-                rule_name: rule_name,
-                sign: sign,
-                id: rule_id,
-            }),
-        )
-    }
-
-    pub fn add_fact_node(&mut self, name: String) -> NodeIndex {
         self.graph
-            .add_node(ADGNode::ADGFactNode(ADGFactNode { name: name }))
+            .add_edge(start_node, end_node, ADGEdge::ADGRelationalEdge(rel_edge))
+    }
+
+    /// Add an edge from every body atom of the rule to every head atom
+    pub fn add_rule_edges(&mut self, rule: &Rule) -> Result<(), MetamorphicError> {
+        for (sign, body_atoms) in body_atoms_by_sign(rule) {
+            for (body_index, body_atom) in body_atoms.into_iter().enumerate() {
+                let start_node: NodeIndex = self.get_rel_node_tag(&body_atom.predicate())?;
+                for (head_index, head_atom) in rule.head().iter().enumerate() {
+                    let end_node: NodeIndex = self.get_rel_node_tag(&head_atom.predicate())?;
+                    let rel_edge = ADGRelationalEdge::new(
+                        rule,
+                        sign.clone(),
+                        body_index,
+                        body_atom,
+                        head_index,
+                        head_atom,
+                    );
+                    self.add_rel_edge(start_node, end_node, rel_edge);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The names of the rules with edges in the ADG, sorted.
+    /// With `without_existentials` only rules without existential variables in any head atom.
+    pub fn get_rule_names(&self, without_existentials: bool) -> Vec<String> {
+        let mut rule_names: BTreeMap<String, bool> = BTreeMap::new();
+        for edge in self.graph.edge_references() {
+            if let ADGEdge::ADGRelationalEdge(rel_edge) = edge.weight() {
+                if let Some(rule_name) = &rel_edge.rule_name {
+                    *rule_names.entry(rule_name.clone()).or_default() |=
+                        !rel_edge.features.existential_variables.is_empty();
+                }
+            }
+        }
+        rule_names
+            .into_iter()
+            .filter(|(_, has_existentials)| !without_existentials || !has_existentials)
+            .map(|(rule_name, _)| rule_name)
+            .collect()
+    }
+
+    /// Remove the edges of the rule with this name, e.g. before adding the edges of its rewritten form
+    pub fn remove_rule_edges(&mut self, rule_name: &str) {
+        self.graph.retain_edges(|graph, edge| match &graph[edge] {
+            ADGEdge::ADGRelationalEdge(rel_edge) => {
+                rel_edge.rule_name.as_deref() != Some(rule_name)
+            }
+            ADGEdge::ADGFactEdge(_) => true,
+        });
+    }
+
+    /// The relational edges of the rule with this name, with their body and head nodes
+    pub fn get_rule_edges(
+        &self,
+        rule_name: &str,
+    ) -> Vec<(NodeIndex, NodeIndex, &ADGRelationalEdge)> {
+        self.graph
+            .edge_references()
+            .filter_map(|edge| match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge)
+                    if rel_edge.rule_name.as_deref() == Some(rule_name) =>
+                {
+                    Some((edge.source(), edge.target(), rel_edge))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the relation is derived by rules only, none of which is recursive,
+    /// has existential variables or aggregates in the head atoms of the relation.
+    /// Facts or imports of the relation, also non-ground ones, have fact edges into it.
+    pub fn is_defined_by_plain_rules(&self, node: NodeIndex) -> bool {
+        let mut has_rule: bool = false;
+        for edge in self
+            .graph
+            .edges_directed(node, petgraph::Direction::Incoming)
+        {
+            match edge.weight() {
+                ADGEdge::ADGFactEdge(_) => return false,
+                ADGEdge::ADGRelationalEdge(rel_edge) => {
+                    if !rel_edge.features.existential_variables.is_empty()
+                        || rel_edge.features.is_non_monotone()
+                        || self.has_path(node, edge.source())
+                    {
+                        return false;
+                    }
+                    has_rule = true;
+                }
+            }
+        }
+        has_rule
+    }
+
+    /// Whether `to` can be reached from `from`, i.e. `from` is used to derive `to`.
+    /// A node reaches itself.
+    pub fn has_path(&self, from: NodeIndex, to: NodeIndex) -> bool {
+        has_path_connecting(&self.graph, from, to, None)
+    }
+
+    pub fn add_fact_node(&mut self, fact_node: ADGFactNode) -> NodeIndex {
+        self.graph.add_node(ADGNode::ADGFactNode(fact_node))
+    }
+
+    /// Get the fact nodes (not imports) with a fact edge into the relation's node, with their terms
+    pub fn get_fact_nodes(
+        &self,
+        tag: &Tag,
+    ) -> Result<Vec<(NodeIndex, Vec<GroundTerm>)>, MetamorphicError> {
+        let mut fact_nodes: Vec<(NodeIndex, Vec<GroundTerm>)> = Vec::new();
+        for edge in self.get_node_edges(tag, petgraph::Direction::Incoming)? {
+            if let Some(ADGNode::ADGFactNode(ADGFactNode::Fact(terms))) =
+                self.graph.node_weight(edge.source())
+            {
+                fact_nodes.push((edge.source(), terms.clone()));
+            }
+        }
+        Ok(fact_nodes)
+    }
+
+    /// Get the import nodes with a fact edge into the relation's node, with their imports
+    pub fn get_import_nodes(
+        &self,
+        tag: &Tag,
+    ) -> Result<Vec<(NodeIndex, ImportDescriptor)>, MetamorphicError> {
+        let mut import_nodes: Vec<(NodeIndex, ImportDescriptor)> = Vec::new();
+        for edge in self.get_node_edges(tag, petgraph::Direction::Incoming)? {
+            if let Some(ADGNode::ADGFactNode(ADGFactNode::Import(import))) =
+                self.graph.node_weight(edge.source())
+            {
+                import_nodes.push((edge.source(), import.clone()));
+            }
+        }
+        Ok(import_nodes)
+    }
+
+    /// Get a fact node of the relation with exactly these terms
+    pub fn find_fact_node(&self, tag: &Tag, terms: &[GroundTerm]) -> Option<NodeIndex> {
+        self.get_fact_nodes(tag)
+            .ok()?
+            .into_iter()
+            .find(|(_, fact_terms)| same_terms(fact_terms, terms))
+            .map(|(fact_node, _)| fact_node)
+    }
+
+    /// Remove a fact node together with its fact edge
+    pub fn remove_fact_node(&mut self, fact_node: NodeIndex) -> Result<(), MetamorphicError> {
+        match self.graph.node_weight(fact_node) {
+            None => return Err(MetamorphicError::UnknownNode(fact_node.index())),
+            Some(ADGNode::ADGRelationalNode(_)) => {
+                return Err(MetamorphicError::ExpectedFactNode(fact_node.index()));
+            }
+            Some(ADGNode::ADGFactNode(_)) => (),
+        }
+        self.graph.remove_node(fact_node);
+        // The last node took the index of the removed one
+        if let Some(ADGNode::ADGRelationalNode(rel_node)) = self.graph.node_weight(fact_node) {
+            self.predicate_ids.insert(rel_node.tag.clone(), fact_node);
+        }
+        Ok(())
     }
 
     pub fn add_fact_edge(&mut self, fact_node: NodeIndex, rel_node: NodeIndex) {
@@ -672,8 +1598,10 @@ impl<'a> AnnotatedDependencyGraph {
             .add_edge(fact_node, rel_node, ADGEdge::ADGFactEdge(ADGFactEdge {}));
     }
 
-    fn get_fact_node(&self, rule: components::rule::Rule) -> Option<ADGFactNode> {
-        todo!()
+    /// Get a fact node of the fact, i.e. one with the same terms
+    /// and a fact edge into the relational node of the fact's predicate
+    pub fn get_fact_node(&self, fact: &Fact) -> Option<NodeIndex> {
+        self.find_fact_node(fact.predicate(), &fact_ground_terms(fact)?)
     }
 
     fn build_rel_edges(&self) {
@@ -780,19 +1708,787 @@ impl<'a> AnnotatedDependencyGraph {
         vec
     }
 
-    fn get_output_rel_node(&self) -> Option<ADGRelationalNode> {
-        todo!()
+    /// Get the relational node of the output predicate, if one was chosen already
+    pub fn get_output_rel_node(&self) -> Option<&ADGRelationalNode> {
+        self.get_rel_node(self.output_predicate.as_ref()?).ok()
     }
 
-    fn check_one_rel_node_for_each_rel(&self) -> bool {
-        todo!()
+    /// Cross-check the ADG against the program.
+    /// Returns a description of every inconsistency, empty if the ADG matches the program.
+    pub fn validate(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        problems.extend(self.check_one_rel_node_for_each_rel(program));
+        problems.extend(self.check_arities(program));
+        problems.extend(self.check_one_fact_node_for_each_fact(program));
+        problems.extend(self.check_each_fact_node_has_at_least_one_outgoing_edge());
+        problems.extend(self.check_one_rel_edge_for_each_body_atom(program));
+        problems.extend(self.check_output_rel_node(program));
+        problems
     }
 
-    fn check_one_fact_node_for_each_fact(&self) -> bool {
-        todo!()
+    /// Build the ADG of the program from scratch, with my output predicate, and compare
+    /// it to myself up to node index renumbering. Returns a readable line per difference,
+    /// empty if both ADGs have the same structure, annotations and compatible column types.
+    /// Relational nodes without edges are kept, as no statement of the program may use them yet.
+    pub fn diff_with_rebuilt(
+        &self,
+        program: &ProgramHandle,
+    ) -> Result<Vec<String>, MetamorphicError> {
+        let mut rebuilt: AnnotatedDependencyGraph = Self::from_program(program)?;
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] {
+                let isolated: bool = self.graph.neighbors_undirected(node).next().is_none();
+                if isolated && !rebuilt.has_rel_node(&rel_node.tag) {
+                    rebuilt.add_rel_node(&rel_node.tag);
+                }
+            }
+        }
+        if let Some(output_predicate) = &self.output_predicate {
+            rebuilt.set_output_rel(output_predicate);
+            rebuilt.calculate_ancestry_and_inverse_stratum()?;
+        }
+
+        let mut differences: Vec<String> = Vec::new();
+        if self.output_predicate != rebuilt.output_predicate {
+            let name = |tag: &Option<Tag>| match tag {
+                Some(tag) => tag.name().to_string(),
+                None => String::from("none"),
+            };
+            differences.push(format!(
+                "output predicate differs: {} maintained, {} rebuilt",
+                name(&self.output_predicate),
+                name(&rebuilt.output_predicate)
+            ));
+        }
+
+        // Relational nodes are identified by their tag
+        let rel_nodes = |adg: &AnnotatedDependencyGraph| -> BTreeMap<String, ADGRelationalNode> {
+            adg.graph
+                .node_weights()
+                .filter_map(|node| match node {
+                    ADGNode::ADGRelationalNode(rel_node) => {
+                        Some((rel_node.tag.name().to_string(), rel_node.clone()))
+                    }
+                    ADGNode::ADGFactNode(_) => None,
+                })
+                .collect()
+        };
+        let maintained_rel_nodes = rel_nodes(self);
+        let rebuilt_rel_nodes = rel_nodes(&rebuilt);
+        for (name, rel_node) in &maintained_rel_nodes {
+            let Some(rebuilt_node) = rebuilt_rel_nodes.get(name) else {
+                differences.push(format!("only in the maintained ADG: relation {name}"));
+                continue;
+            };
+            if rebuilt_node.ancestry != rel_node.ancestry
+                || rebuilt_node.inverse_stratum != rel_node.inverse_stratum
+            {
+                differences.push(format!(
+                    "relation {name} differs: {rel_node:?} maintained, {rebuilt_node:?} rebuilt"
+                ));
+            }
+            // Column types are only ever widened, so the maintained ones may be more general
+            match (&rel_node.column_types, &rebuilt_node.column_types) {
+                (Some(maintained), Some(rebuilt)) if maintained.len() != rebuilt.len() => {
+                    differences.push(format!(
+                        "relation {name} has arity {} maintained, {} rebuilt",
+                        maintained.len(),
+                        rebuilt.len()
+                    ))
+                }
+                (Some(maintained), Some(rebuilt))
+                    if maintained
+                        .iter()
+                        .zip(rebuilt)
+                        .any(|(maintained, rebuilt)| !maintained.fits(*rebuilt)) =>
+                {
+                    differences.push(format!(
+                        "relation {name} has column types {maintained:?} maintained, {rebuilt:?} rebuilt"
+                    ))
+                }
+                _ => (),
+            }
+        }
+        for name in rebuilt_rel_nodes.keys() {
+            if !maintained_rel_nodes.contains_key(name) {
+                differences.push(format!("only in the rebuilt ADG: relation {name}"));
+            }
+        }
+
+        // Fact nodes and edges are compared as multisets of their descriptions
+        let maintained_descriptions = self.describe_facts_and_edges();
+        let rebuilt_descriptions = rebuilt.describe_facts_and_edges();
+        let mut counts: BTreeMap<&String, i64> = BTreeMap::new();
+        for description in &maintained_descriptions {
+            *counts.entry(description).or_insert(0) += 1;
+        }
+        for description in &rebuilt_descriptions {
+            *counts.entry(description).or_insert(0) -= 1;
+        }
+        for (description, count) in counts {
+            if count > 0 {
+                differences.push(format!(
+                    "only in the maintained ADG ({count}x): {description}"
+                ));
+            } else if count < 0 {
+                differences.push(format!(
+                    "only in the rebuilt ADG ({}x): {description}",
+                    -count
+                ));
+            }
+        }
+        Ok(differences)
     }
 
-    fn check_each_fact_node_has_at_least_one_outgoing_edge(&self) -> bool {
-        todo!()
+    /// Describe fact nodes and relational edges independently of node indices
+    fn describe_facts_and_edges(&self) -> Vec<String> {
+        let mut descriptions: Vec<String> = Vec::new();
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGFactNode(fact_node) = &self.graph[node] {
+                let mut targets: Vec<String> = self
+                    .graph
+                    .edges_directed(node, petgraph::Direction::Outgoing)
+                    .map(|edge| match self.get_tag_of_node(edge.target()) {
+                        Some(tag) => tag.name().to_string(),
+                        None => format!("fact node {}", edge.target().index()),
+                    })
+                    .collect();
+                targets.sort();
+                descriptions.push(format!("fact node {fact_node} into {}", targets.join(", ")));
+            }
+        }
+        for edge in self.graph.edge_references() {
+            if let ADGEdge::ADGRelationalEdge(rel_edge) = edge.weight() {
+                let tag_name = |node: NodeIndex| match self.get_tag_of_node(node) {
+                    Some(tag) => tag.name().to_string(),
+                    None => format!("fact node {}", node.index()),
+                };
+                descriptions.push(format!(
+                    "edge {} -> {} of {rel_edge} with rule id {}",
+                    tag_name(edge.source()),
+                    tag_name(edge.target()),
+                    rel_edge.id
+                ));
+            }
+        }
+        descriptions
+    }
+
+    /// The tag of a relational node, None for fact nodes
+    fn get_tag_of_node(&self, node: NodeIndex) -> Option<&Tag> {
+        match self.graph.node_weight(node)? {
+            ADGNode::ADGRelationalNode(rel_node) => Some(&rel_node.tag),
+            ADGNode::ADGFactNode(_) => None,
+        }
+    }
+
+    /// Every predicate of the program has exactly one relational node, registered under its tag.
+    /// Relational nodes the program does not use (yet) are fine.
+    fn check_one_rel_node_for_each_rel(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        let mut num_rel_nodes: HashMap<&Tag, usize> = HashMap::new();
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] {
+                *num_rel_nodes.entry(&rel_node.tag).or_insert(0) += 1;
+                if self.predicate_ids.get(&rel_node.tag) != Some(&node) {
+                    problems.push(format!(
+                        "Relational node {} of {} is not registered under its tag",
+                        node.index(),
+                        rel_node.tag
+                    ));
+                }
+            }
+        }
+        for (tag, num) in num_rel_nodes {
+            if num > 1 {
+                problems.push(format!("Relation {tag} has {num} relational nodes"));
+            }
+        }
+        for (tag, node) in &self.predicate_ids {
+            if self.get_tag_of_node(*node) != Some(tag) {
+                problems.push(format!(
+                    "Relation {tag} is registered under node {}, which is not its relational node",
+                    node.index()
+                ));
+            }
+        }
+        let mut predicates: Vec<Tag> = program.all_predicates().into_iter().collect();
+        predicates.sort_by(|a, b| a.name().cmp(b.name()));
+        for tag in predicates {
+            if !self.predicate_ids.contains_key(&tag) {
+                problems.push(format!(
+                    "Relation {tag} of the program has no relational node"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Every relational node has the arity nemo computed for its relation
+    fn check_arities(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        let mut arities: Vec<(Tag, usize)> = program.arities().into_iter().collect();
+        arities.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+        for (tag, arity) in arities {
+            if let Ok(rel_node) = self.get_rel_node(&tag) {
+                if rel_node.arity() != Some(arity) {
+                    problems.push(format!(
+                        "Relation {tag} has arity {arity}, but its relational node has arity {:?}",
+                        rel_node.arity()
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Every fact and import of the program has its own fact node with a fact edge into
+    /// the relational node of its predicate, and there are no other fact nodes.
+    fn check_one_fact_node_for_each_fact(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        // The fact nodes no fact or import has claimed yet
+        let mut unclaimed: Vec<(NodeIndex, &Tag, &ADGFactNode)> = Vec::new();
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGFactNode(fact_node) = &self.graph[node] {
+                for edge in self
+                    .graph
+                    .edges_directed(node, petgraph::Direction::Outgoing)
+                {
+                    if let Some(tag) = self.get_tag_of_node(edge.target()) {
+                        unclaimed.push((node, tag, fact_node));
+                    }
+                }
+            }
+        }
+        for statement in program.statements() {
+            let (predicate, expected, description): (&Tag, ADGFactNode, String) = match statement {
+                statement::Statement::Fact(fact) => (
+                    fact.predicate(),
+                    ADGFactNode::from_fact(fact),
+                    format!("Fact {fact}"),
+                ),
+                statement::Statement::Import(import) => (
+                    import.predicate(),
+                    ADGFactNode::Import(ImportDescriptor::from_import(import)),
+                    format!("Import of {}", import.predicate()),
+                ),
+                _ => continue,
+            };
+            let position: Option<usize> =
+                unclaimed
+                    .iter()
+                    .position(|(_, tag, fact_node)| match (&expected, fact_node) {
+                        _ if *tag != predicate => false,
+                        (ADGFactNode::Fact(terms), ADGFactNode::Fact(fact_terms)) => {
+                            same_terms(terms, fact_terms)
+                        }
+                        (
+                            ADGFactNode::NonGroundFact(fact),
+                            ADGFactNode::NonGroundFact(node_fact),
+                        ) => fact == node_fact,
+                        (ADGFactNode::Import(import), ADGFactNode::Import(node_import)) => {
+                            import == node_import
+                        }
+                        _ => false,
+                    });
+            match position {
+                Some(position) => {
+                    unclaimed.swap_remove(position);
+                }
+                None => problems.push(format!("{description} has no fact node")),
+            }
+        }
+        for (node, tag, fact_node) in unclaimed {
+            problems.push(format!(
+                "Fact node {} {:?} of {tag} belongs to no fact or import of the program",
+                node.index(),
+                fact_node
+            ));
+        }
+        problems
+    }
+
+    /// Every fact node has a fact edge into a relational node and no incoming edges.
+    /// Fact edges only go from fact nodes to relational nodes,
+    /// relational edges only between relational nodes.
+    fn check_each_fact_node_has_at_least_one_outgoing_edge(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGFactNode(fact_node) = &self.graph[node] {
+                let num_outgoing: usize = self
+                    .graph
+                    .edges_directed(node, petgraph::Direction::Outgoing)
+                    .count();
+                if num_outgoing == 0 {
+                    problems.push(format!(
+                        "Fact node {} {:?} has no outgoing edge",
+                        node.index(),
+                        fact_node
+                    ));
+                }
+                let num_incoming: usize = self
+                    .graph
+                    .edges_directed(node, petgraph::Direction::Incoming)
+                    .count();
+                if num_incoming > 0 {
+                    problems.push(format!(
+                        "Fact node {} {:?} has {num_incoming} incoming edges",
+                        node.index(),
+                        fact_node
+                    ));
+                }
+            }
+        }
+        for edge in self.graph.edge_references() {
+            let source_is_rel: bool = self.get_tag_of_node(edge.source()).is_some();
+            let target_is_rel: bool = self.get_tag_of_node(edge.target()).is_some();
+            let valid: bool = match edge.weight() {
+                ADGEdge::ADGFactEdge(_) => !source_is_rel && target_is_rel,
+                ADGEdge::ADGRelationalEdge(_) => source_is_rel && target_is_rel,
+            };
+            if !valid {
+                problems.push(format!(
+                    "Edge {:?} from node {} to node {} connects the wrong kind of nodes",
+                    edge.weight(),
+                    edge.source().index(),
+                    edge.target().index()
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Every body atom of every rule has a relational edge of the same sign and with
+    /// the same terms into each head atom of the rule, and there are no other relational edges.
+    fn check_one_rel_edge_for_each_body_atom(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        // The relational edges no body atom has claimed yet
+        let mut unclaimed: Vec<(EdgeIndex, &Tag, &Tag, &ADGRelationalEdge)> = Vec::new();
+        for edge in self.graph.edge_references() {
+            if let ADGEdge::ADGRelationalEdge(rel_edge) = edge.weight() {
+                if let (Some(source), Some(target)) = (
+                    self.get_tag_of_node(edge.source()),
+                    self.get_tag_of_node(edge.target()),
+                ) {
+                    unclaimed.push((edge.id(), source, target, rel_edge));
+                }
+            }
+        }
+        for rule in program.rules() {
+            let rule_name: String = rule.name().unwrap_or(String::from("without name"));
+            for (sign, body_atoms) in body_atoms_by_sign(rule) {
+                for (body_index, body_atom) in body_atoms.into_iter().enumerate() {
+                    for (head_index, head_atom) in rule.head().iter().enumerate() {
+                        let expected = ADGRelationalEdge::new(
+                            rule,
+                            sign.clone(),
+                            body_index,
+                            body_atom,
+                            head_index,
+                            head_atom,
+                        );
+                        let position: Option<usize> =
+                            unclaimed.iter().position(|(_, source, target, rel_edge)| {
+                                **source == body_atom.predicate()
+                                    && **target == head_atom.predicate()
+                                    && rel_edge.sign == expected.sign
+                                    && rel_edge.rule_name == expected.rule_name
+                                    && rel_edge.body_index == body_index
+                                    && rel_edge.head_index == head_index
+                            });
+                        let Some(position) = position else {
+                            problems.push(format!(
+                                "Rule {rule_name} has no edge from {} to {}: {expected}",
+                                body_atom.predicate(),
+                                head_atom.predicate()
+                            ));
+                            continue;
+                        };
+                        let (_, _, _, rel_edge) = unclaimed.swap_remove(position);
+                        if rel_edge.id != expected.id {
+                            problems.push(format!(
+                                "Edge {rel_edge} has rule id {}, but the rule has id {}",
+                                rel_edge.id, expected.id
+                            ));
+                        }
+                        if rel_edge.body_terms != expected.body_terms
+                            || rel_edge.head_terms != expected.head_terms
+                            || rel_edge.features != expected.features
+                        {
+                            problems.push(format!(
+                                "Edge {rel_edge} does not match the atoms of the rule: {expected}"
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for (edge, source, target, rel_edge) in unclaimed {
+            problems.push(format!(
+                "Edge {} from {source} to {target} belongs to no rule of the program: {rel_edge}",
+                edge.index()
+            ));
+        }
+        problems
+    }
+
+    /// The output predicate has a relational node and is exported by the program
+    fn check_output_rel_node(&self, program: &ProgramHandle) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        match &self.output_predicate {
+            None => problems.push(String::from("No output predicate set")),
+            Some(tag) => {
+                if self.get_output_rel_node().is_none() {
+                    problems.push(format!("Output predicate {tag} has no relational node"));
+                }
+                if !program.exports().any(|export| export.predicate() == tag) {
+                    problems.push(format!(
+                        "Output predicate {tag} is not exported by the program"
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::rule_model::{
+        components::tag::Tag,
+        pipeline::id::ProgramComponentId,
+        programs::{ProgramRead, handle::ProgramHandle},
+    };
+    use petgraph::graph::NodeIndex;
+
+    use super::{
+        ADGNode, ADGRelationalEdge, Ancestry, AnnotatedDependencyGraph, ColumnType, EdgeFeatures,
+        ImportDescriptor, Sign, import_attribute, import_column_types,
+    };
+    use crate::{
+        transformation_sequence::load_program, transformations::name_rules::TransformationNameRules,
+    };
+
+    const COLUMN_TYPES: [ColumnType; 6] = [
+        ColumnType::Unknown,
+        ColumnType::Int,
+        ColumnType::Double,
+        ColumnType::Float,
+        ColumnType::String,
+        ColumnType::Any,
+    ];
+
+    #[test]
+    fn column_type_join_is_commutative() {
+        for column_type in COLUMN_TYPES {
+            for other in COLUMN_TYPES {
+                assert_eq!(column_type.join(other), other.join(column_type));
+            }
+        }
+    }
+
+    #[test]
+    fn column_type_join_is_idempotent() {
+        for column_type in COLUMN_TYPES {
+            assert_eq!(column_type.join(column_type), column_type);
+        }
+    }
+
+    #[test]
+    fn column_type_join_is_the_least_common_type() {
+        for column_type in COLUMN_TYPES {
+            assert_eq!(ColumnType::Unknown.join(column_type), column_type);
+            assert_eq!(ColumnType::Any.join(column_type), ColumnType::Any);
+        }
+        assert_eq!(ColumnType::Int.join(ColumnType::String), ColumnType::Any);
+        assert_eq!(ColumnType::Double.join(ColumnType::Float), ColumnType::Any);
+    }
+
+    #[test]
+    fn column_type_fits() {
+        // Unknown and Any columns take values of every type
+        for column_type in COLUMN_TYPES {
+            assert!(ColumnType::Unknown.fits(column_type));
+            assert!(ColumnType::Any.fits(column_type));
+            assert!(column_type.fits(ColumnType::Unknown));
+            assert!(column_type.fits(ColumnType::Any));
+        }
+        // Known types only take values of the same type
+        let known = [
+            ColumnType::Int,
+            ColumnType::Double,
+            ColumnType::Float,
+            ColumnType::String,
+        ];
+        for column_type in known {
+            for other in known {
+                assert_eq!(column_type.fits(other), column_type == other);
+            }
+        }
+    }
+
+    /// Parse the program and name its rules, like a transformation sequence does
+    fn named_program(name: &str, source: &str) -> ProgramHandle {
+        let path = std::env::temp_dir().join(format!("{name}.rls"));
+        std::fs::write(&path, source).unwrap();
+        let (program, _) = load_program(&path).unwrap();
+        let Ok(program) = program.transform(TransformationNameRules::new()) else {
+            panic!("Could not name the rules of {name}");
+        };
+        program
+    }
+
+    /// A named program with facts, an import, negation and an aggregate,
+    /// and its ADG with `degree` as output predicate
+    fn example_program_and_adg(name: &str) -> (ProgramHandle, AnnotatedDependencyGraph) {
+        let program = named_program(
+            name,
+            r#"@import edge :- csv { resource = "edge.csv", format = (int, int) } .
+node(1) .
+node(2) .
+blocked(2) .
+reach(?x) :- node(?x), ~blocked(?x) .
+reach(?y) :- reach(?x), edge(?x, ?y) .
+degree(?x, #count(?y)) :- reach(?x), edge(?x, ?y) .
+@export degree :- csv {} .
+"#,
+        );
+        let mut adg = AnnotatedDependencyGraph::from_program(&program).unwrap();
+        adg.set_output_rel(&Tag::new(String::from("degree")));
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+        (program, adg)
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_adg() {
+        let (program, adg) = example_program_and_adg("adg_json_round_trip");
+        let json = adg.to_json().unwrap();
+        let rebuilt = AnnotatedDependencyGraph::from_json(&json, &program).unwrap();
+        assert_eq!(rebuilt.to_json().unwrap(), json);
+    }
+
+    #[test]
+    fn unchanged_adg_has_no_differences() {
+        let (program, adg) = example_program_and_adg("adg_diff_unchanged");
+        assert!(adg.diff_with_rebuilt(&program).unwrap().is_empty());
+    }
+
+    #[test]
+    fn edited_adg_differences_name_the_relation() {
+        let (program, mut adg) = example_program_and_adg("adg_diff_edited");
+        let blocked = adg
+            .get_rel_node_tag(&Tag::new(String::from("blocked")))
+            .unwrap();
+        let reach = adg
+            .get_rel_node_tag(&Tag::new(String::from("reach")))
+            .unwrap();
+        if let ADGNode::ADGRelationalNode(rel_node) = &mut adg.graph_mut()[blocked] {
+            rel_node.ancestry = Some(Ancestry::Positive);
+        }
+        if let ADGNode::ADGRelationalNode(rel_node) = &mut adg.graph_mut()[reach] {
+            rel_node.column_types = Some(vec![ColumnType::Int, ColumnType::Int]);
+        }
+
+        let differences = adg.diff_with_rebuilt(&program).unwrap();
+        assert_eq!(differences.len(), 2);
+        assert!(differences[0].starts_with("relation blocked differs"));
+        assert_eq!(
+            differences[1],
+            "relation reach has arity 2 maintained, 1 rebuilt"
+        );
+    }
+
+    /// An import as nemo prints it
+    const PRINTED_IMPORT: &str =
+        r#"@import edge :- csv {resource="edge.csv",format=(int,skip,any)} ."#;
+
+    #[test]
+    fn import_attribute_reads_the_printed_value() {
+        assert_eq!(
+            import_attribute(PRINTED_IMPORT, "resource"),
+            Some(r#""edge.csv",format=(int,skip,any)} ."#)
+        );
+        assert_eq!(
+            import_attribute(PRINTED_IMPORT, "format"),
+            Some("(int,skip,any)} .")
+        );
+        assert_eq!(import_attribute(PRINTED_IMPORT, "delimiter"), None);
+    }
+
+    #[test]
+    fn import_attribute_skips_longer_keys_and_values() {
+        let directive = r#"@import r :- dsv {my_resource="resource",resource="r.dsv"} ."#;
+        assert_eq!(
+            import_attribute(directive, "resource"),
+            Some(r#""r.dsv"} ."#)
+        );
+    }
+
+    #[test]
+    fn import_column_types_skip_skipped_columns() {
+        assert_eq!(
+            import_column_types(PRINTED_IMPORT),
+            Some(vec![ColumnType::Int, ColumnType::Any])
+        );
+        assert_eq!(
+            import_column_types(r#"@import edge :- csv {resource="edge.csv"} ."#),
+            None
+        );
+    }
+
+    #[test]
+    fn import_descriptor_of_printed_import() {
+        let import = ImportDescriptor::from_directive(PRINTED_IMPORT.to_string());
+        assert_eq!(import.format, "csv");
+        assert_eq!(import.resource.as_deref(), Some("edge.csv"));
+    }
+
+    /// The import helpers read the directive as nemo prints it,
+    /// so this fails if nemo changes the printed form
+    #[test]
+    fn nemo_prints_imports_as_expected() {
+        let (program, _) = example_program_and_adg("adg_printed_import");
+        let import = program.imports().next().unwrap();
+        assert_eq!(
+            ImportDescriptor::from_import(import),
+            ImportDescriptor {
+                format: String::from("csv"),
+                resource: Some(String::from("edge.csv")),
+                directive: import.to_string(),
+            }
+        );
+        assert_eq!(
+            import_column_types(&import.to_string()),
+            Some(vec![ColumnType::Int, ColumnType::Int])
+        );
+    }
+
+    /// An ADG with one relational node per name and no edges, the first one is the output
+    fn adg_with_output(names: &[&str]) -> (AnnotatedDependencyGraph, Vec<NodeIndex>) {
+        let mut adg = AnnotatedDependencyGraph::default();
+        let nodes: Vec<NodeIndex> = names
+            .iter()
+            .map(|name| adg.add_rel_node(&Tag::new(name.to_string())))
+            .collect();
+        adg.set_output_rel(&Tag::new(names[0].to_string()));
+        (adg, nodes)
+    }
+
+    fn add_edge(
+        adg: &mut AnnotatedDependencyGraph,
+        body: NodeIndex,
+        head: NodeIndex,
+        sign: Sign,
+        features: EdgeFeatures,
+    ) {
+        let rel_edge = ADGRelationalEdge {
+            rule_name: Some(format!("r_{}_{}", body.index(), head.index())),
+            id: ProgramComponentId::new(0),
+            sign,
+            body_index: 0,
+            head_index: 0,
+            body_terms: Vec::new(),
+            head_terms: Vec::new(),
+            shared_variables: Vec::new(),
+            features,
+        };
+        adg.add_rel_edge(body, head, rel_edge);
+    }
+
+    fn annotation(
+        adg: &AnnotatedDependencyGraph,
+        node: NodeIndex,
+    ) -> (Option<Ancestry>, Option<u32>) {
+        adg.get_annotation(node).unwrap()
+    }
+
+    #[test]
+    fn ancestry_lattice_order() {
+        for ancestry in [Ancestry::Positive, Ancestry::Negative] {
+            assert!(Ancestry::None < ancestry);
+            assert!(ancestry < Ancestry::Unknown);
+        }
+        assert!(Ancestry::None < Ancestry::Unknown);
+        assert_eq!(Ancestry::Positive.partial_cmp(&Ancestry::Negative), None);
+    }
+
+    #[test]
+    fn ancestry_is_joined_over_all_paths() {
+        let (mut adg, nodes) = adg_with_output(&["out", "a", "b", "c", "d"]);
+        let (out, a, b, c, d) = (nodes[0], nodes[1], nodes[2], nodes[3], nodes[4]);
+        add_edge(&mut adg, a, out, Sign::Positive, EdgeFeatures::default());
+        add_edge(&mut adg, b, out, Sign::Negative, EdgeFeatures::default());
+        // c reaches the output positively through a and negatively through b
+        add_edge(&mut adg, c, a, Sign::Positive, EdgeFeatures::default());
+        add_edge(&mut adg, c, b, Sign::Positive, EdgeFeatures::default());
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+
+        assert_eq!(annotation(&adg, out), (Some(Ancestry::Positive), Some(0)));
+        assert_eq!(annotation(&adg, a), (Some(Ancestry::Positive), Some(0)));
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::Negative), Some(1)));
+        assert_eq!(annotation(&adg, c), (Some(Ancestry::Unknown), Some(1)));
+        // The output does not depend on d
+        assert_eq!(annotation(&adg, d), (Some(Ancestry::None), None));
+    }
+
+    #[test]
+    fn inverse_stratum_rises_across_negative_edges() {
+        let (mut adg, nodes) = adg_with_output(&["out", "a", "b", "c"]);
+        let (out, a, b, c) = (nodes[0], nodes[1], nodes[2], nodes[3]);
+        add_edge(&mut adg, a, out, Sign::Negative, EdgeFeatures::default());
+        add_edge(&mut adg, b, a, Sign::Negative, EdgeFeatures::default());
+        add_edge(&mut adg, c, b, Sign::Positive, EdgeFeatures::default());
+        // The longer path decides the inverse stratum of c
+        add_edge(&mut adg, c, out, Sign::Positive, EdgeFeatures::default());
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+
+        assert_eq!(annotation(&adg, a), (Some(Ancestry::Negative), Some(1)));
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::Positive), Some(2)));
+        assert_eq!(annotation(&adg, c), (Some(Ancestry::Positive), Some(2)));
+    }
+
+    #[test]
+    fn recomputing_after_removing_edges_lowers_the_annotations() {
+        let (mut adg, nodes) = adg_with_output(&["out", "a", "b"]);
+        let (out, a, b) = (nodes[0], nodes[1], nodes[2]);
+        add_edge(&mut adg, a, out, Sign::Positive, EdgeFeatures::default());
+        add_edge(&mut adg, a, b, Sign::Negative, EdgeFeatures::default());
+        add_edge(&mut adg, b, out, Sign::Positive, EdgeFeatures::default());
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+        assert_eq!(annotation(&adg, a), (Some(Ancestry::Unknown), Some(1)));
+
+        adg.remove_rule_edges(&format!("r_{}_{}", a.index(), b.index()));
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+        assert_eq!(annotation(&adg, a), (Some(Ancestry::Positive), Some(0)));
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::Positive), Some(0)));
+    }
+
+    #[test]
+    fn update_pushes_the_annotation_to_new_predecessors() {
+        let (mut adg, nodes) = adg_with_output(&["out", "a", "b"]);
+        let (out, a, b) = (nodes[0], nodes[1], nodes[2]);
+        add_edge(&mut adg, a, out, Sign::Positive, EdgeFeatures::default());
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::None), None));
+
+        add_edge(&mut adg, b, a, Sign::Negative, EdgeFeatures::default());
+        adg.update_ancestry_and_inverse_stratum(a).unwrap();
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::Negative), Some(1)));
+    }
+
+    #[test]
+    fn aggregate_edge_gives_unknown_ancestry() {
+        let (mut adg, nodes) = adg_with_output(&["out", "a", "b"]);
+        let (out, a, b) = (nodes[0], nodes[1], nodes[2]);
+        let aggregate = EdgeFeatures {
+            aggregates: vec![String::from("#count(?x)")],
+            ..EdgeFeatures::default()
+        };
+        add_edge(&mut adg, a, out, Sign::Positive, aggregate);
+        add_edge(&mut adg, b, a, Sign::Positive, EdgeFeatures::default());
+        adg.calculate_ancestry_and_inverse_stratum().unwrap();
+
+        assert_eq!(annotation(&adg, a), (Some(Ancestry::Unknown), Some(0)));
+        assert_eq!(annotation(&adg, b), (Some(Ancestry::Unknown), Some(0)));
     }
 }
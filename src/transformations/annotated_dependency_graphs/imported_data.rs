@@ -0,0 +1,247 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use nemo::rule_model::components::tag::Tag;
+
+use super::{
+    ADGFactNode, ADGNode, AnnotatedDependencyGraph, ColumnType, ImportDescriptor, import_attribute,
+};
+use crate::error::MetamorphicError;
+
+/// The rows of an imported csv or tsv file, so transformations can add and remove them.
+/// Rows are kept as the fields of the file, not as ground terms,
+/// because nemo interprets the fields according to the `format` of the import.
+#[derive(Clone, Debug)]
+pub struct ImportedData {
+    /// Where the rows were read from
+    path: PathBuf,
+    delimiter: u8,
+    gzipped: bool,
+    /// The type of each column of the file, including skipped columns
+    column_types: Vec<ColumnType>,
+    rows: Vec<Vec<String>>,
+    /// Whether a row was added or removed since the file was read
+    modified: bool,
+}
+impl ImportedData {
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+    pub fn column_types(&self) -> &[ColumnType] {
+        &self.column_types
+    }
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    fn read(path: PathBuf, delimiter: u8) -> Result<Self, MetamorphicError> {
+        let gzipped: bool = path.extension().is_some_and(|extension| extension == "gz");
+        let file: File = File::open(&path).map_err(|_| MetamorphicError::ReadData(path.clone()))?;
+        let reader: Box<dyn Read> = match gzipped {
+            true => Box::new(GzDecoder::new(file)),
+            false => Box::new(file),
+        };
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(reader);
+        for record in csv_reader.records() {
+            let record = record.map_err(|_| MetamorphicError::ReadData(path.clone()))?;
+            rows.push(record.iter().map(|field| field.to_string()).collect());
+        }
+        Ok(ImportedData {
+            path,
+            delimiter,
+            gzipped,
+            column_types: Vec::new(),
+            rows,
+            modified: false,
+        })
+    }
+
+    fn write(&self, path: &Path) -> Result<(), MetamorphicError> {
+        let file: File = File::create(path).map_err(|_| MetamorphicError::Write(path.into()))?;
+        let writer: Box<dyn Write> = match self.gzipped {
+            true => Box::new(GzEncoder::new(file, Compression::default())),
+            false => Box::new(file),
+        };
+        let mut csv_writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_writer(writer);
+        for row in &self.rows {
+            csv_writer
+                .write_record(row)
+                .map_err(|_| MetamorphicError::Write(path.into()))?;
+        }
+        csv_writer
+            .flush()
+            .map_err(|_| MetamorphicError::Write(path.into()))
+    }
+}
+
+/// The delimiter of an import whose data can be loaded, None for other formats
+fn import_delimiter(import: &ImportDescriptor) -> Option<u8> {
+    match import.format.as_str() {
+        "csv" => Some(b','),
+        "tsv" => Some(b'\t'),
+        "dsv" => import_attribute(&import.directive, "delimiter")?
+            .strip_prefix('"')?
+            .bytes()
+            .next(),
+        _ => None,
+    }
+}
+
+/// The column types of the file from the `format` of the import,
+/// or the column types of the relation if it has none
+fn file_column_types(
+    import: &ImportDescriptor,
+    relation_column_types: Option<Vec<ColumnType>>,
+) -> Vec<ColumnType> {
+    let format_column_types: Option<Vec<ColumnType>> =
+        import_attribute(&import.directive, "format")
+            .and_then(|value| value.strip_prefix('('))
+            .and_then(|value| Some(&value[..value.find(')')?]))
+            .map(|formats| {
+                formats
+                    .split(',')
+                    .map(|format| {
+                        ColumnType::of_import_format(format.trim()).unwrap_or(ColumnType::Unknown)
+                    })
+                    .collect()
+            });
+    format_column_types
+        .or(relation_column_types)
+        .unwrap_or_default()
+}
+
+impl AnnotatedDependencyGraph {
+    /// Read the rows of every csv, tsv and dsv import, also gzipped ones.
+    /// Relative resources are resolved against the import folder, like nemo does.
+    /// Imports from URLs and of other formats are left alone.
+    pub fn load_imports(&mut self, import_folder: &Path) -> Result<(), MetamorphicError> {
+        for tag in self.predicates.clone() {
+            let relation_column_types: Option<Vec<ColumnType>> = self.get_column_types(&tag)?;
+            for (_, import) in self.get_import_nodes(&tag)? {
+                let (Some(resource), Some(delimiter)) =
+                    (import.resource.clone(), import_delimiter(&import))
+                else {
+                    continue;
+                };
+                if resource.contains("://") || self.imported_data.contains_key(&resource) {
+                    continue;
+                }
+                let mut data: ImportedData =
+                    ImportedData::read(import_folder.join(&resource), delimiter)?;
+                data.column_types = file_column_types(&import, relation_column_types.clone());
+                if data.column_types.is_empty() {
+                    data.column_types =
+                        vec![ColumnType::Unknown; data.rows.first().map_or(0, |row| row.len())];
+                }
+                println!("Loaded {} rows of {}", data.rows.len(), data.path.display());
+                self.imported_data.insert(resource, data);
+            }
+        }
+        Ok(())
+    }
+
+    /// The loaded data of an imported resource
+    pub fn get_imported_data(&self, resource: &str) -> Option<&ImportedData> {
+        self.imported_data.get(resource)
+    }
+
+    /// The resources with loaded data, each with the relations that import it
+    pub fn get_imported_resources(&self) -> Vec<(String, Vec<Tag>)> {
+        self.imported_data
+            .keys()
+            .map(|resource| {
+                let tags: Vec<Tag> = self
+                    .graph
+                    .node_indices()
+                    .filter(|node| {
+                        matches!(
+                            &self.graph[*node],
+                            ADGNode::ADGFactNode(ADGFactNode::Import(import))
+                                if import.resource.as_deref() == Some(resource.as_str())
+                        )
+                    })
+                    .flat_map(|node| self.graph.neighbors(node))
+                    .filter_map(|rel_node| self.get_tag_of_node(rel_node).cloned())
+                    .collect();
+                (resource.clone(), tags)
+            })
+            .collect()
+    }
+
+    /// Append a row to the loaded data of a resource
+    pub fn add_imported_row(
+        &mut self,
+        resource: &str,
+        row: Vec<String>,
+    ) -> Result<(), MetamorphicError> {
+        let Some(data) = self.imported_data.get_mut(resource) else {
+            return Err(MetamorphicError::UnknownImport(resource.to_string()));
+        };
+        data.rows.push(row);
+        data.modified = true;
+        Ok(())
+    }
+
+    /// Remove the first row with these fields from the loaded data of a resource.
+    /// Returns whether there was such a row.
+    pub fn remove_imported_row(
+        &mut self,
+        resource: &str,
+        row: &[String],
+    ) -> Result<bool, MetamorphicError> {
+        let Some(data) = self.imported_data.get_mut(resource) else {
+            return Err(MetamorphicError::UnknownImport(resource.to_string()));
+        };
+        let Some(position) = data.rows.iter().position(|data_row| data_row == row) else {
+            return Ok(false);
+        };
+        data.rows.remove(position);
+        data.modified = true;
+        Ok(true)
+    }
+
+    /// Whether rows were added to or removed from any loaded data
+    pub fn has_modified_imports(&self) -> bool {
+        self.imported_data.values().any(ImportedData::is_modified)
+    }
+
+    /// Write the modified data into `folder`, under the relative path of the resource.
+    /// Returns each modified resource with the absolute path of its copy.
+    pub fn write_modified_imports(
+        &self,
+        folder: &Path,
+    ) -> Result<Vec<(String, PathBuf)>, MetamorphicError> {
+        let mut copies: Vec<(String, PathBuf)> = Vec::new();
+        for (resource, data) in &self.imported_data {
+            if !data.modified {
+                continue;
+            }
+            // Keep only the plain components, so the copy stays in the folder
+            let relative: PathBuf = Path::new(resource)
+                .components()
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect();
+            let path: PathBuf = folder.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|_| MetamorphicError::Write(parent.into()))?;
+            }
+            data.write(&path)?;
+            let path: PathBuf = std::path::absolute(&path).unwrap_or(path);
+            copies.push((resource.clone(), path));
+        }
+        Ok(copies)
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use petgraph::{graph::NodeIndex, visit::EdgeRef};
+
+use super::{
+    ADGEdge, ADGNode, ADGRelationalEdge, ADGRelationalNode, Ancestry, AnnotatedDependencyGraph,
+    Sign,
+};
+
+/// Fill colour of a relational node with the given ancestry
+fn ancestry_colour(ancestry: Option<Ancestry>) -> &'static str {
+    match ancestry {
+        None => "#ffffff",
+        Some(Ancestry::None) => "#e0e0e0",
+        Some(Ancestry::Positive) => "#b7e4c7",
+        Some(Ancestry::Negative) => "#ffc9b9",
+        Some(Ancestry::Unknown) => "#d7c6f0",
+    }
+}
+
+/// Name of the ancestry in tooltips and as Mermaid class
+fn ancestry_name(ancestry: Option<Ancestry>) -> &'static str {
+    match ancestry {
+        None => "unset",
+        Some(Ancestry::None) => "none",
+        Some(Ancestry::Positive) => "positive",
+        Some(Ancestry::Negative) => "negative",
+        Some(Ancestry::Unknown) => "unknown",
+    }
+}
+
+fn relational_label(rel_node: &ADGRelationalNode) -> String {
+    match rel_node.inverse_stratum {
+        Some(inverse_stratum) => format!("{} [{}]", rel_node.tag.name(), inverse_stratum),
+        None => rel_node.tag.name().to_string(),
+    }
+}
+
+/// The rule name of an edge, with the features of the rule below it
+fn edge_label(rel_edge: &ADGRelationalEdge) -> String {
+    let rule_name: &str = rel_edge.rule_name.as_deref().unwrap_or("unnamed rule");
+    match rel_edge.features.is_empty() {
+        true => rule_name.to_string(),
+        false => format!("{rule_name}\n{}", rel_edge.features),
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_mermaid(label: &str) -> String {
+    label.replace('"', "#quot;").replace('\n', " ")
+}
+
+impl AnnotatedDependencyGraph {
+    /// The relational nodes grouped by inverse stratum, nodes without one under None
+    fn rel_nodes_by_inverse_stratum(&self) -> BTreeMap<Option<u32>, Vec<NodeIndex>> {
+        let mut strata: BTreeMap<Option<u32>, Vec<NodeIndex>> = BTreeMap::new();
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] {
+                strata
+                    .entry(rel_node.inverse_stratum)
+                    .or_default()
+                    .push(node);
+            }
+        }
+        strata
+    }
+
+    fn is_output_rel_node(&self, rel_node: &ADGRelationalNode) -> bool {
+        self.output_predicate.as_ref() == Some(&rel_node.tag)
+    }
+
+    /// Render the ADG in the DOT language of graphviz.
+    /// Relational nodes are ellipses coloured by ancestry and clustered by inverse stratum,
+    /// fact nodes are boxes, negative edges are dashed and red and
+    /// the output relation has a thick double border.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph adg {\n");
+        dot += "    rankdir=LR;\n";
+        dot += "    node [fontname=\"Helvetica\"];\n";
+        dot += "    edge [fontname=\"Helvetica\", fontsize=10];\n";
+
+        for (inverse_stratum, nodes) in self.rel_nodes_by_inverse_stratum() {
+            let indent: &str = match inverse_stratum {
+                Some(inverse_stratum) => {
+                    dot += &format!("    subgraph cluster_stratum_{inverse_stratum} {{\n");
+                    dot += &format!("        label=\"inverse stratum {inverse_stratum}\";\n");
+                    dot += "        style=rounded;\n        color=\"#9e9e9e\";\n";
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes {
+                let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] else {
+                    continue;
+                };
+                let output_style: &str = match self.is_output_rel_node(rel_node) {
+                    true => ", peripheries=2, penwidth=3",
+                    false => "",
+                };
+                dot += &format!(
+                    "{indent}n{} [label=\"{}\", shape=ellipse, style=filled, fillcolor=\"{}\", tooltip=\"ancestry {}\"{output_style}];\n",
+                    node.index(),
+                    escape_dot(&relational_label(rel_node)),
+                    ancestry_colour(rel_node.ancestry),
+                    ancestry_name(rel_node.ancestry),
+                );
+            }
+            if inverse_stratum.is_some() {
+                dot += "    }\n";
+            }
+        }
+
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGFactNode(fact_node) = &self.graph[node] {
+                dot += &format!(
+                    "    n{} [label=\"{}\", shape=box, style=\"rounded,filled\", fillcolor=\"#fff3bf\"];\n",
+                    node.index(),
+                    escape_dot(&fact_node.to_string()),
+                );
+            }
+        }
+
+        for edge in self.graph.edge_references() {
+            let attributes: String = match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge) => {
+                    let label: String = escape_dot(&edge_label(rel_edge));
+                    match rel_edge.sign {
+                        Sign::Positive => format!("label=\"{label}\""),
+                        Sign::Negative => {
+                            format!("label=\"{label}\", style=dashed, color=red, fontcolor=red")
+                        }
+                    }
+                }
+                ADGEdge::ADGFactEdge(_) => String::from("color=\"#9e9e9e\", arrowhead=empty"),
+            };
+            dot += &format!(
+                "    n{} -> n{} [{attributes}];\n",
+                edge.source().index(),
+                edge.target().index()
+            );
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    /// Render the ADG as a Mermaid flowchart, styled like `to_dot`
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart LR\n");
+        let mut classes: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+
+        for (inverse_stratum, nodes) in self.rel_nodes_by_inverse_stratum() {
+            let indent: &str = match inverse_stratum {
+                Some(inverse_stratum) => {
+                    mermaid += &format!(
+                        "    subgraph stratum_{inverse_stratum}[\"inverse stratum {inverse_stratum}\"]\n"
+                    );
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes {
+                let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] else {
+                    continue;
+                };
+                mermaid += &format!(
+                    "{indent}n{}([\"{}\"])\n",
+                    node.index(),
+                    escape_mermaid(&relational_label(rel_node))
+                );
+                classes
+                    .entry(ancestry_name(rel_node.ancestry))
+                    .or_default()
+                    .push(format!("n{}", node.index()));
+                if self.is_output_rel_node(rel_node) {
+                    classes
+                        .entry("output")
+                        .or_default()
+                        .push(format!("n{}", node.index()));
+                }
+            }
+            if inverse_stratum.is_some() {
+                mermaid += "    end\n";
+            }
+        }
+
+        for node in self.graph.node_indices() {
+            if let ADGNode::ADGFactNode(fact_node) = &self.graph[node] {
+                mermaid += &format!(
+                    "    n{}[\"{}\"]\n",
+                    node.index(),
+                    escape_mermaid(&fact_node.to_string())
+                );
+                classes
+                    .entry("fact")
+                    .or_default()
+                    .push(format!("n{}", node.index()));
+            }
+        }
+
+        // Mermaid styles links by their position
+        let mut negative_links: Vec<String> = Vec::new();
+        for (position, edge) in self.graph.edge_references().enumerate() {
+            let (source, target) = (edge.source().index(), edge.target().index());
+            match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge) => {
+                    let label: String = escape_mermaid(&edge_label(rel_edge));
+                    match rel_edge.sign {
+                        Sign::Positive => {
+                            mermaid += &format!("    n{source} -->|\"{label}\"| n{target}\n")
+                        }
+                        Sign::Negative => {
+                            mermaid += &format!("    n{source} -.->|\"{label}\"| n{target}\n");
+                            negative_links.push(position.to_string());
+                        }
+                    }
+                }
+                ADGEdge::ADGFactEdge(_) => mermaid += &format!("    n{source} --> n{target}\n"),
+            }
+        }
+
+        for ancestry in [
+            None,
+            Some(Ancestry::None),
+            Some(Ancestry::Positive),
+            Some(Ancestry::Negative),
+            Some(Ancestry::Unknown),
+        ] {
+            mermaid += &format!(
+                "    classDef {} fill:{},stroke:#424242\n",
+                ancestry_name(ancestry),
+                ancestry_colour(ancestry)
+            );
+        }
+        mermaid += "    classDef fact fill:#fff3bf,stroke:#9e9e9e\n";
+        mermaid += "    classDef output stroke-width:4px\n";
+        for (class, nodes) in classes {
+            mermaid += &format!("    class {} {class}\n", nodes.join(","));
+        }
+        if !negative_links.is_empty() {
+            mermaid += &format!(
+                "    linkStyle {} stroke:red,color:red\n",
+                negative_links.join(",")
+            );
+        }
+        mermaid
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::{Display, Formatter},
+};
+
+use nemo::rule_model::components::tag::Tag;
+use petgraph::{
+    Direction,
+    algo::tarjan_scc,
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+};
+
+use super::{ADGEdge, ADGNode, AnnotatedDependencyGraph, Sign};
+use crate::error::MetamorphicError;
+
+/// One relational edge of a dependency cycle
+#[derive(Clone, Debug)]
+pub struct CycleEdge {
+    pub body: Tag,
+    pub head: Tag,
+    pub rule_name: Option<String>,
+    pub sign: Sign,
+}
+
+/// A cycle of relational edges, each edge starts at the head of the previous one
+#[derive(Clone, Debug)]
+pub struct DependencyCycle {
+    pub edges: Vec<CycleEdge>,
+}
+impl Display for DependencyCycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let Some(first) = self.edges.first() else {
+            return f.write_str("(empty cycle)");
+        };
+        f.write_fmt(format_args!("{}", first.body))?;
+        for edge in &self.edges {
+            f.write_fmt(format_args!(
+                " -[{} {:?}]-> {}",
+                edge.rule_name.as_deref().unwrap_or("unnamed rule"),
+                edge.sign,
+                edge.head
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// The forward strata of a stratifiable program: a relation is in a higher stratum than
+/// the relations it depends on negatively and in no lower stratum than those it depends on
+/// positively. Relations that depend on nothing are in stratum 0.
+#[derive(Clone, Debug)]
+pub struct Stratification {
+    strata: HashMap<Tag, u32>,
+}
+impl Stratification {
+    pub fn num_strata(&self) -> u32 {
+        self.strata
+            .values()
+            .max()
+            .map_or(0, |max_stratum| max_stratum + 1)
+    }
+}
+impl Display for Stratification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_fmt(format_args!("{} strata\n", self.num_strata()))?;
+        let mut strata: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+        for (tag, stratum) in &self.strata {
+            strata.entry(*stratum).or_default().push(tag.name());
+        }
+        for (stratum, mut names) in strata {
+            names.sort();
+            f.write_fmt(format_args!("Stratum {stratum}: {}\n", names.join(", ")))?;
+        }
+        Ok(())
+    }
+}
+
+impl AnnotatedDependencyGraph {
+    /// Compute the forward stratum of every relation from the strongly connected
+    /// components of the ADG. Fails with the offending cycle if a relation
+    /// depends negatively on itself.
+    pub fn stratify(&self) -> Result<Stratification, MetamorphicError> {
+        if let Some(cycle) = self.find_negative_cycle() {
+            return Err(MetamorphicError::NegativeCycle(cycle));
+        }
+        let mut node_strata: HashMap<NodeIndex, u32> = HashMap::new();
+        // Tarjan lists the components in reverse topological order, so we start at the
+        // components that no other component depends on
+        for component in tarjan_scc(&self.graph).into_iter().rev() {
+            let mut stratum: u32 = 0;
+            for node in &component {
+                for edge in self.graph.edges_directed(*node, Direction::Incoming) {
+                    let ADGEdge::ADGRelationalEdge(rel_edge) = edge.weight() else {
+                        continue;
+                    };
+                    // Edges within the component are positive
+                    let Some(body_stratum) = node_strata.get(&edge.source()) else {
+                        continue;
+                    };
+                    stratum = stratum.max(match rel_edge.sign {
+                        Sign::Positive => *body_stratum,
+                        Sign::Negative => body_stratum + 1,
+                    });
+                }
+            }
+            for node in component {
+                node_strata.insert(node, stratum);
+            }
+        }
+
+        let mut strata: HashMap<Tag, u32> = HashMap::new();
+        for (node, stratum) in node_strata {
+            if let ADGNode::ADGRelationalNode(rel_node) = &self.graph[node] {
+                strata.insert(rel_node.tag.clone(), stratum);
+            }
+        }
+        Ok(Stratification { strata })
+    }
+
+    /// A cycle through a negative edge, if the program is not stratifiable
+    pub fn find_negative_cycle(&self) -> Option<DependencyCycle> {
+        let mut components: HashMap<NodeIndex, usize> = HashMap::new();
+        for (component_index, component) in tarjan_scc(&self.graph).into_iter().enumerate() {
+            for node in component {
+                components.insert(node, component_index);
+            }
+        }
+        // A negative edge within a component closes a cycle with
+        // a path from its head back to its body
+        self.graph
+            .edge_references()
+            .find_map(|edge| match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge)
+                    if rel_edge.sign == Sign::Negative
+                        && components.get(&edge.source()) == components.get(&edge.target()) =>
+                {
+                    let mut edges: Vec<EdgeIndex> = vec![edge.id()];
+                    edges.extend(self.shortest_path(edge.target(), edge.source())?);
+                    Some(self.dependency_cycle(&edges))
+                }
+                _ => None,
+            })
+    }
+
+    /// Whether a new relational edge from `body` to `head` would close a cycle through
+    /// a negative edge, i.e. make the program unstratifiable.
+    /// Rule-adding transformations ask this before they commit a new rule.
+    pub fn would_break_stratification(&self, body: NodeIndex, head: NodeIndex, sign: Sign) -> bool {
+        let depends_on_head: HashSet<NodeIndex> = self.reachable(head, Direction::Outgoing);
+        if !depends_on_head.contains(&body) {
+            // No cycle at all
+            return false;
+        }
+        if sign == Sign::Negative {
+            return true;
+        }
+        // The new positive edge closes cycles through all paths from head to body
+        let body_depends_on: HashSet<NodeIndex> = self.reachable(body, Direction::Incoming);
+        self.graph
+            .edge_references()
+            .any(|edge| match edge.weight() {
+                ADGEdge::ADGRelationalEdge(rel_edge) => {
+                    rel_edge.sign == Sign::Negative
+                        && depends_on_head.contains(&edge.source())
+                        && body_depends_on.contains(&edge.target())
+                }
+                ADGEdge::ADGFactEdge(_) => false,
+            })
+    }
+
+    /// The nodes reachable from `start` in the direction, including `start`
+    fn reachable(&self, start: NodeIndex, direction: Direction) -> HashSet<NodeIndex> {
+        let mut reached: HashSet<NodeIndex> = HashSet::from([start]);
+        let mut worklist: VecDeque<NodeIndex> = VecDeque::from([start]);
+        while let Some(node) = worklist.pop_front() {
+            for neighbour in self.graph.neighbors_directed(node, direction) {
+                if reached.insert(neighbour) {
+                    worklist.push_back(neighbour);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The edges of a shortest path from `from` to `to`, empty if they are the same node
+    fn shortest_path(&self, from: NodeIndex, to: NodeIndex) -> Option<Vec<EdgeIndex>> {
+        let mut reached_by: HashMap<NodeIndex, Option<EdgeIndex>> = HashMap::from([(from, None)]);
+        let mut worklist: VecDeque<NodeIndex> = VecDeque::from([from]);
+        while let Some(node) = worklist.pop_front() {
+            if node == to {
+                let mut path: Vec<EdgeIndex> = Vec::new();
+                let mut current: NodeIndex = to;
+                while let Some(Some(edge)) = reached_by.get(&current) {
+                    path.push(*edge);
+                    current = self.graph.edge_endpoints(*edge)?.0;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for edge in self.graph.edges_directed(node, Direction::Outgoing) {
+                if !reached_by.contains_key(&edge.target()) {
+                    reached_by.insert(edge.target(), Some(edge.id()));
+                    worklist.push_back(edge.target());
+                }
+            }
+        }
+        None
+    }
+
+    fn dependency_cycle(&self, edges: &[EdgeIndex]) -> DependencyCycle {
+        let edges: Vec<CycleEdge> = edges
+            .iter()
+            .filter_map(|edge| {
+                let (body, head) = self.graph.edge_endpoints(*edge)?;
+                let ADGEdge::ADGRelationalEdge(rel_edge) = &self.graph[*edge] else {
+                    return None;
+                };
+                Some(CycleEdge {
+                    body: self.get_tag_of_node(body)?.clone(),
+                    head: self.get_tag_of_node(head)?.clone(),
+                    rule_name: rel_edge.rule_name.clone(),
+                    sign: rel_edge.sign.clone(),
+                })
+            })
+            .collect();
+        DependencyCycle { edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::rule_model::{components::tag::Tag, pipeline::id::ProgramComponentId};
+    use petgraph::graph::NodeIndex;
+
+    use super::super::{ADGRelationalEdge, AnnotatedDependencyGraph, EdgeFeatures, Sign};
+
+    /// An ADG with one relational node per name and no edges
+    fn adg_with_relations(names: &[&str]) -> (AnnotatedDependencyGraph, Vec<NodeIndex>) {
+        let mut adg = AnnotatedDependencyGraph::default();
+        let nodes: Vec<NodeIndex> = names
+            .iter()
+            .map(|name| adg.add_rel_node(&Tag::new(name.to_string())))
+            .collect();
+        (adg, nodes)
+    }
+
+    fn add_edge(adg: &mut AnnotatedDependencyGraph, body: NodeIndex, head: NodeIndex, sign: Sign) {
+        let rel_edge = ADGRelationalEdge {
+            rule_name: Some(format!("r_{}_{}", body.index(), head.index())),
+            id: ProgramComponentId::new(0),
+            sign,
+            body_index: 0,
+            head_index: 0,
+            body_terms: Vec::new(),
+            head_terms: Vec::new(),
+            shared_variables: Vec::new(),
+            features: EdgeFeatures::default(),
+        };
+        adg.add_rel_edge(body, head, rel_edge);
+    }
+
+    #[test]
+    fn positive_cycle_is_stratifiable() {
+        let (mut adg, nodes) = adg_with_relations(&["a", "b"]);
+        add_edge(&mut adg, nodes[0], nodes[1], Sign::Positive);
+        add_edge(&mut adg, nodes[1], nodes[0], Sign::Positive);
+        assert!(adg.find_negative_cycle().is_none());
+        let stratification = adg.stratify().expect("positive cycles are stratifiable");
+        assert_eq!(stratification.num_strata(), 1);
+    }
+
+    #[test]
+    fn negative_edge_raises_the_stratum() {
+        let (mut adg, nodes) = adg_with_relations(&["a", "b"]);
+        add_edge(&mut adg, nodes[0], nodes[1], Sign::Negative);
+        assert!(adg.find_negative_cycle().is_none());
+        let stratification = adg.stratify().expect("no cycle at all");
+        assert_eq!(stratification.num_strata(), 2);
+    }
+
+    #[test]
+    fn negative_self_loop_is_a_negative_cycle() {
+        let (mut adg, nodes) = adg_with_relations(&["a"]);
+        add_edge(&mut adg, nodes[0], nodes[0], Sign::Negative);
+        let cycle = adg
+            .find_negative_cycle()
+            .expect("a depends negatively on itself");
+        assert_eq!(cycle.edges.len(), 1);
+        assert_eq!(cycle.edges[0].body.name(), "a");
+        assert_eq!(cycle.edges[0].head.name(), "a");
+        assert!(cycle.edges[0].sign == Sign::Negative);
+        assert!(adg.stratify().is_err());
+    }
+
+    #[test]
+    fn negative_edge_inside_positive_component_is_a_negative_cycle() {
+        let (mut adg, nodes) = adg_with_relations(&["a", "b", "c"]);
+        add_edge(&mut adg, nodes[0], nodes[1], Sign::Negative);
+        add_edge(&mut adg, nodes[1], nodes[2], Sign::Positive);
+        add_edge(&mut adg, nodes[2], nodes[0], Sign::Positive);
+        let cycle = adg
+            .find_negative_cycle()
+            .expect("a depends negatively on itself");
+        // The cycle starts with the negative edge and leads back to its body
+        assert_eq!(cycle.edges.len(), 3);
+        assert!(cycle.edges[0].sign == Sign::Negative);
+        assert_eq!(cycle.edges[0].body.name(), "a");
+        assert_eq!(cycle.edges[2].head.name(), "a");
+        for pair in cycle.edges.windows(2) {
+            assert_eq!(pair[0].head, pair[1].body);
+        }
+    }
+
+    #[test]
+    fn edge_closing_a_negative_cycle_breaks_stratification() {
+        let (mut adg, nodes) = adg_with_relations(&["a", "b", "c", "d"]);
+        add_edge(&mut adg, nodes[0], nodes[1], Sign::Negative);
+        add_edge(&mut adg, nodes[1], nodes[2], Sign::Positive);
+        // c -> a closes the cycle a -> b -> c -> a through the negative edge
+        assert!(adg.would_break_stratification(nodes[2], nodes[0], Sign::Positive));
+        assert!(adg.would_break_stratification(nodes[2], nodes[0], Sign::Negative));
+        // d -> a closes no cycle
+        assert!(!adg.would_break_stratification(nodes[3], nodes[0], Sign::Negative));
+        // c -> b closes the positive cycle b -> c -> b only
+        assert!(!adg.would_break_stratification(nodes[2], nodes[1], Sign::Positive));
+        // but a negative c -> b closes a negative one
+        assert!(adg.would_break_stratification(nodes[2], nodes[1], Sign::Negative));
+
+        add_edge(&mut adg, nodes[2], nodes[0], Sign::Positive);
+        assert!(adg.find_negative_cycle().is_some());
+    }
+}
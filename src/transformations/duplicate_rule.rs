@@ -0,0 +1,112 @@
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::commit::ProgramCommit;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::ProgramWrite;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::seq::IndexedRandom;
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util;
+
+/// Add a copy of an existing rule under a new `r_` name.
+/// The copy derives nothing new, so the program stays equivalent,
+/// but nemo derives every tuple of the rule twice.
+/// Its edges are parallel to the edges of the original rule, so
+/// ancestry and strata stay the same.
+/// Rules with existential variables are not copied, the copy could
+/// invent other nulls than the original.
+pub struct DuplicateRule<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    _rng: &'b mut rand_chacha::ChaCha8Rng,
+    rule_name: String,
+    /// The name of the copy when replaying a recorded step
+    planned_copy_name: Option<String>,
+}
+
+impl<'a, 'b> DuplicateRule<'a, 'b> {
+    /// Copy the rule of a recorded step.
+    /// Returns None if the ADG has no such rule (anymore).
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        rule_name: String,
+        copy_name: String,
+    ) -> Option<Self> {
+        if !adg.get_rule_names(true).contains(&rule_name) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            _rng: rng,
+            rule_name,
+            planned_copy_name: Some(copy_name),
+        })
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for DuplicateRule<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(rule_name) = adg.get_rule_names(true).choose(rng).cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            rule_name,
+            planned_copy_name: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!adg.get_rule_names(true).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for DuplicateRule<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        // Copy the program
+        let mut commit: ProgramCommit = program.fork_full();
+
+        let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), program) else {
+            println!("Rule {} has vanished from the program", self.rule_name);
+            return commit.submit();
+        };
+        let copy_name: String = self
+            .planned_copy_name
+            .take()
+            .unwrap_or_else(|| util::get_new_rule_name(program));
+        let mut copy: Rule = rule.clone();
+        copy.set_name(&copy_name);
+        commit.add_rule(copy);
+        let new_program: ProgramHandle = commit.submit()?;
+
+        // The copy only has its id once it is part of the program
+        if let Some(copy) = util::fetch_rule_by_name(copy_name.clone(), &new_program) {
+            if let Err(error) = self.adg.add_rule_edges(copy) {
+                self.adg.record_failed_update(format!(
+                    "Failed to add the edges of a copied rule to the ADG: {error}"
+                ));
+            }
+        }
+        println!("Copied rule {} to {}", self.rule_name, copy_name);
+        self.adg.record_step(TransformationStep::DuplicateRule {
+            rule_name: self.rule_name,
+            copy_name,
+        });
+
+        Ok(new_program)
+    }
+}
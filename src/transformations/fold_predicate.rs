@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+
+use nemo::rule_model::components::IterableVariables;
+use nemo::rule_model::components::atom::Atom;
+use nemo::rule_model::components::literal::Literal;
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::components::term::Term;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::{AnnotatedDependencyGraph, Sign};
+use crate::transformations::serialization;
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util;
+
+/// Fold some body literals of a rule into a new relation, the reverse of `UnfoldRule`:
+/// a new rule derives the new relation from the folded literals, and the rule
+/// gets one atom over the new relation in their place. The new relation projects
+/// exactly the variables the folded literals share with the rest of the rule,
+/// so the rule derives the same tuples, but nemo materialises one more relation.
+/// Rules with existential variables or aggregates are not folded, fewer body variables
+/// could change the nulls they invent or the groups they aggregate.
+pub struct FoldPredicate<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    rng: &'b mut rand_chacha::ChaCha8Rng,
+    rule_name: String,
+    /// The folding when replaying a recorded step
+    planned: Option<Folding>,
+}
+
+/// Which body literals are folded and into which new relation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Folding {
+    /// Positions of the folded literals in the body
+    pub folded: Vec<usize>,
+    /// The new relation
+    #[serde(with = "serialization::tag")]
+    pub relation: Tag,
+    /// Name of the new rule deriving the new relation
+    pub folding_rule_name: String,
+}
+
+impl<'a, 'b> FoldPredicate<'a, 'b> {
+    /// The rules with a positive body atom and without existential variables or aggregates
+    fn eligible_rules(adg: &AnnotatedDependencyGraph) -> Vec<String> {
+        adg.get_rule_names(true)
+            .into_iter()
+            .filter(|rule_name| {
+                let edges = adg.get_rule_edges(rule_name);
+                edges
+                    .iter()
+                    .any(|(_, _, rel_edge)| rel_edge.sign == Sign::Positive)
+                    && edges
+                        .iter()
+                        .all(|(_, _, rel_edge)| !rel_edge.features.is_non_monotone())
+            })
+            .collect()
+    }
+
+    /// Fold the literals of a recorded step.
+    /// Returns None if the rule can't be folded (anymore) or the new relation already exists.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        rule_name: String,
+        folding: Folding,
+    ) -> Option<Self> {
+        if !Self::eligible_rules(adg).contains(&rule_name) || adg.has_rel_node(&folding.relation) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: Some(folding),
+        })
+    }
+
+    /// Fold a random positive body atom together with every other body literal
+    /// with probability 1/2. Negative atoms and operations are only folded if
+    /// the folded positive atoms bind their variables, to keep the new rule safe.
+    /// None if the rule has no positive body atom.
+    fn plan_folding(&mut self, rule: &Rule, program: &ProgramHandle) -> Option<Folding> {
+        let positive: Vec<usize> = (0..rule.body().len())
+            .filter(|index| matches!(rule.body()[*index], Literal::Positive(_)))
+            .collect();
+        let seed: usize = *positive.choose(self.rng)?;
+        let chosen: Vec<usize> = (0..rule.body().len())
+            .filter(|index| *index == seed || self.rng.random_bool(0.5))
+            .collect();
+        let bound: HashSet<String> = chosen
+            .iter()
+            .filter(|index| positive.contains(index))
+            .flat_map(|index| variable_names(&rule.body()[*index]))
+            .collect();
+        let folded: Vec<usize> = chosen
+            .into_iter()
+            .filter(|index| {
+                positive.contains(index)
+                    || variable_names(&rule.body()[*index])
+                        .iter()
+                        .all(|name| bound.contains(name))
+            })
+            .collect();
+        Some(Folding {
+            folded,
+            relation: Tag::new(self.adg.get_new_relation_name(self.rng)),
+            folding_rule_name: util::get_new_rule_name(program),
+        })
+    }
+}
+
+/// The names of the named variables of a literal
+fn variable_names(literal: &Literal) -> Vec<String> {
+    literal
+        .variables()
+        .filter_map(|variable| variable.name())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// The new rule deriving the new relation and the rule with the folded literals
+/// replaced by an atom over the new relation, in place of the first folded literal.
+/// None if the positions are not distinct positions of the body, no positive atom is folded,
+/// a variable of a folded negative atom or operation is not bound by a folded positive atom
+/// or the folded literals share no variable with the rest of the rule.
+fn fold(rule: &Rule, folding: &Folding) -> Option<(Rule, Rule)> {
+    let mut positions: Vec<usize> = folding.folded.clone();
+    positions.sort();
+    positions.dedup();
+    let first_position: usize = *positions.first()?;
+    if positions.len() != folding.folded.len()
+        || positions
+            .iter()
+            .any(|position| *position >= rule.body().len())
+    {
+        return None;
+    }
+    let mut folded: Vec<Literal> = Vec::new();
+    let mut kept: Vec<Literal> = Vec::new();
+    for (position, literal) in rule.body().iter().enumerate() {
+        match positions.contains(&position) {
+            true => folded.push(literal.clone()),
+            false => kept.push(literal.clone()),
+        }
+    }
+
+    let bound: HashSet<String> = folded
+        .iter()
+        .filter(|literal| matches!(literal, Literal::Positive(_)))
+        .flat_map(variable_names)
+        .collect();
+    let safe: bool = folded.iter().all(|literal| {
+        variable_names(literal)
+            .iter()
+            .all(|name| bound.contains(name))
+    });
+    let has_positive: bool = folded
+        .iter()
+        .any(|literal| matches!(literal, Literal::Positive(_)));
+    if !has_positive || !safe {
+        return None;
+    }
+
+    // The folded variables used outside the folded literals, in order of their first occurrence
+    let outside: HashSet<String> = rule
+        .head()
+        .iter()
+        .flat_map(|atom| atom.variables())
+        .filter_map(|variable| variable.name())
+        .map(|name| name.to_string())
+        .chain(kept.iter().flat_map(variable_names))
+        .collect();
+    let mut shared: Vec<String> = Vec::new();
+    for name in folded.iter().flat_map(variable_names) {
+        if outside.contains(&name) && !shared.contains(&name) {
+            shared.push(name);
+        }
+    }
+    if shared.is_empty() {
+        return None;
+    }
+    let terms: Vec<Term> = shared
+        .iter()
+        .map(|name| Term::universal_variable(name))
+        .collect();
+
+    let mut folding_rule: Rule = Rule::new(
+        vec![Atom::new(folding.relation.clone(), terms.clone())],
+        folded,
+    );
+    folding_rule.set_name(&folding.folding_rule_name);
+    kept.insert(
+        first_position,
+        Literal::Positive(Atom::new(folding.relation.clone(), terms)),
+    );
+    let mut folded_rule: Rule = rule.clone();
+    *folded_rule.body_mut() = kept;
+    Some((folding_rule, folded_rule))
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for FoldPredicate<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(rule_name) = Self::eligible_rules(adg).choose(rng).cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_rules(adg).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for FoldPredicate<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), program) else {
+            println!("Rule {} has vanished from the program", self.rule_name);
+            return program.fork_full().submit();
+        };
+        let planned: Option<Folding> = match self.planned.take() {
+            Some(planned) => Some(planned),
+            None => self.plan_folding(rule, program),
+        };
+        let Some((folding, (folding_rule, folded_rule))) = planned.and_then(|folding| {
+            let rules: (Rule, Rule) = fold(rule, &folding)?;
+            Some((folding, rules))
+        }) else {
+            println!("Rule {} has no body literals to fold", self.rule_name);
+            return program.fork_full().submit();
+        };
+
+        let new_program: ProgramHandle = util::replace_rule(
+            program,
+            &self.rule_name,
+            &[folding_rule.clone(), folded_rule.clone()],
+        )?;
+
+        self.adg.add_rel_node(&folding.relation);
+        self.adg.remove_rule_edges(&self.rule_name);
+        for rule_name in [&folding.folding_rule_name, &self.rule_name] {
+            if let Some(rule) = util::fetch_rule_by_name(rule_name.clone(), &new_program) {
+                if let Err(error) = self.adg.add_rule_edges(rule) {
+                    self.adg.record_failed_update(format!(
+                        "Failed to add the edges of a folded rule to the ADG: {error}"
+                    ));
+                }
+            }
+        }
+        if let Err(error) = self.adg.merge_head_column_types(&folding_rule) {
+            self.adg.record_failed_update(format!(
+                "Failed to infer the column types of a folded relation: {error}"
+            ));
+        }
+        // Edges were removed, which can lower the annotations, so they are computed again
+        if let Err(error) = self.adg.calculate_ancestry_and_inverse_stratum() {
+            self.adg.record_failed_update(format!(
+                "Failed to update the ADG after folding a rule: {error}"
+            ));
+        }
+        println!(
+            "Folded rule {} into {} and {}",
+            self.rule_name, folding_rule, folded_rule
+        );
+        self.adg.record_step(TransformationStep::FoldPredicate {
+            rule_name: self.rule_name,
+            folding,
+        });
+
+        Ok(new_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::datavalues::AnyDataValue;
+    use nemo::rule_model::components::{
+        atom::Atom,
+        literal::Literal,
+        rule::Rule,
+        tag::Tag,
+        term::{
+            Term,
+            operation::{Operation, operation_kind::OperationKind},
+        },
+    };
+
+    use super::{Folding, fold};
+
+    fn atom(predicate: &str, variables: &[&str]) -> Atom {
+        Atom::new(
+            Tag::new(predicate.to_string()),
+            variables.iter().map(|name| Term::universal_variable(name)),
+        )
+    }
+
+    fn positive(predicate: &str, variables: &[&str]) -> Literal {
+        Literal::Positive(atom(predicate, variables))
+    }
+
+    fn folding(folded: Vec<usize>) -> Folding {
+        Folding {
+            folded,
+            relation: Tag::new("n".to_string()),
+            folding_rule_name: "folding".to_string(),
+        }
+    }
+
+    #[test]
+    fn new_relation_projects_shared_variables_in_order() {
+        // r(?y, ?x) :- e(?x, ?z), f(?z, ?y), g(?y)
+        let rule = Rule::new(
+            vec![atom("r", &["y", "x"])],
+            vec![
+                positive("e", &["x", "z"]),
+                positive("f", &["z", "y"]),
+                positive("g", &["y"]),
+            ],
+        );
+        let (folding_rule, folded_rule) =
+            fold(&rule, &folding(vec![0, 1])).expect("?x and ?y are shared");
+        assert_eq!(folding_rule.name(), Some("folding".to_string()));
+        assert_eq!(folding_rule.head(), &vec![atom("n", &["x", "y"])]);
+        assert_eq!(folding_rule.body(), &rule.body()[..2].to_vec());
+        assert_eq!(folded_rule.head(), rule.head());
+        assert_eq!(
+            folded_rule.body(),
+            &vec![positive("n", &["x", "y"]), positive("g", &["y"])]
+        );
+    }
+
+    #[test]
+    fn new_atom_replaces_the_first_folded_literal() {
+        // r(?x, ?y) :- g(?y), e(?x, ?z), h(?y), f(?z)
+        let rule = Rule::new(
+            vec![atom("r", &["x", "y"])],
+            vec![
+                positive("g", &["y"]),
+                positive("e", &["x", "z"]),
+                positive("h", &["y"]),
+                positive("f", &["z"]),
+            ],
+        );
+        let (folding_rule, folded_rule) = fold(&rule, &folding(vec![3, 1])).expect("?x is shared");
+        assert_eq!(
+            folding_rule.body(),
+            &vec![positive("e", &["x", "z"]), positive("f", &["z"])]
+        );
+        assert_eq!(
+            folded_rule.body(),
+            &vec![
+                positive("g", &["y"]),
+                positive("n", &["x"]),
+                positive("h", &["y"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbound_negative_atom_is_not_folded() {
+        // r(?x, ?y) :- e(?x), ~f(?y), g(?y)
+        let rule = Rule::new(
+            vec![atom("r", &["x", "y"])],
+            vec![
+                positive("e", &["x"]),
+                Literal::Negative(atom("f", &["y"])),
+                positive("g", &["y"]),
+            ],
+        );
+        assert!(fold(&rule, &folding(vec![0, 1])).is_none());
+        assert!(fold(&rule, &folding(vec![1])).is_none());
+        assert!(fold(&rule, &folding(vec![1, 2])).is_some());
+    }
+
+    #[test]
+    fn unbound_operation_is_not_folded() {
+        // r(?x) :- e(?x), g(?y), ?y > 0
+        let rule = Rule::new(
+            vec![atom("r", &["x"])],
+            vec![
+                positive("e", &["x"]),
+                positive("g", &["y"]),
+                Literal::Operation(Operation::new(
+                    OperationKind::NumericGreaterthan,
+                    vec![
+                        Term::universal_variable("y"),
+                        Term::ground(AnyDataValue::new_integer_from_i64(0)),
+                    ],
+                )),
+            ],
+        );
+        assert!(fold(&rule, &folding(vec![0, 2])).is_none());
+        assert!(fold(&rule, &folding(vec![0, 1, 2])).is_some());
+    }
+
+    #[test]
+    fn literals_without_shared_variables_are_not_folded() {
+        // r(?x) :- e(?x), f(?y)
+        let rule = Rule::new(
+            vec![atom("r", &["x"])],
+            vec![positive("e", &["x"]), positive("f", &["y"])],
+        );
+        assert!(fold(&rule, &folding(vec![1])).is_none());
+    }
+
+    #[test]
+    fn invalid_positions_are_rejected() {
+        let rule = Rule::new(
+            vec![atom("r", &["x"])],
+            vec![positive("e", &["x"]), positive("f", &["x"])],
+        );
+        for folded in [vec![], vec![0, 0], vec![2]] {
+            assert!(fold(&rule, &folding(folded)).is_none());
+        }
+    }
+}
@@ -4,6 +4,7 @@ use nemo::rule_model::programs::handle::ProgramHandle;
 use nemo::rule_model::pipeline::transformations::ProgramTransformation;
 use nemo::rule_model::programs::ProgramRead;
 
+use crate::error::MetamorphicError;
 use crate::transformations::MetamorphicTransformation;
 use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
 use crate::transformations::transformation_types::TransformationTypes;
@@ -11,24 +12,35 @@ use crate::transformations::transformation_types::TransformationTypes;
 /// Program transformation
 /// For testing purposes
 // #[derive(Debug, Clone, Copy, Default)]
-pub struct TransformationHelloWorld<'a,'b> {
+pub struct TransformationHelloWorld<'a, 'b> {
     _adg: &'a mut AnnotatedDependencyGraph,
     _rng: &'b mut rand_chacha::ChaCha8Rng,
 }
 
-impl<'a,'b> MetamorphicTransformation<'a,'b> for TransformationHelloWorld<'a,'b> {
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for TransformationHelloWorld<'a, 'b> {
     /* fn fetch_adg(self) -> &'a mut AnnotatedDependencyGraph {
         self.adg
     } */
-    fn new(adg: &'a mut AnnotatedDependencyGraph, rng: &'b mut rand_chacha::ChaCha8Rng, _t : TransformationTypes) -> Option<Self> {
-        Some(Self {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _t: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        Ok(Some(Self {
             _adg: adg,
             _rng: rng,
-        })
+        }))
+    }
+
+    fn is_applicable(
+        _adg: &AnnotatedDependencyGraph,
+        _t: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(true)
     }
 }
 
-impl<'a,'b> ProgramTransformation for TransformationHelloWorld<'a,'b> {
+impl<'a, 'b> ProgramTransformation for TransformationHelloWorld<'a, 'b> {
     fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
         let mut commit = program.fork();
         /* let a = strategy::RuleSelectionStrategy::new(rules);
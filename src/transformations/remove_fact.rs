@@ -0,0 +1,163 @@
+use nemo::rule_model::components::fact::Fact;
+use nemo::rule_model::components::statement::Statement;
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::components::term::primitive::ground::GroundTerm;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::commit::ProgramCommit;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::ProgramRead;
+
+use petgraph::graph::NodeIndex;
+use rand::seq::IndexedRandom;
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util::{fact_ground_terms, same_terms};
+
+/// Remove a fact statement together with its fact node and fact edge.
+/// The inverse of `AddFactNodeAndEdge`:
+/// Oracle depends on ancestry of the fact's relational node.
+pub struct RemoveFact<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    _rng: &'b mut rand_chacha::ChaCha8Rng,
+    chosen_rel_node: Tag,
+    chosen_fact_node: NodeIndex,
+    terms: Vec<GroundTerm>,
+}
+
+impl<'a, 'b> RemoveFact<'a, 'b> {
+    /// The relational nodes facts can be removed from under the transformation type
+    fn eligible_rel_nodes(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<Tag> {
+        match transformation_type {
+            TransformationTypes::EQU => adg.get_none_ancestry_relational_nodes(),
+            TransformationTypes::CON => adg.get_leq_positive_ancestry_relational_nodes(),
+            TransformationTypes::EXP => adg.get_leq_negative_ancestry_relational_nodes(),
+        }
+    }
+
+    /// The facts that can be removed, with the relation and fact node they belong to
+    fn eligible_facts(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<Vec<(Tag, NodeIndex, Vec<GroundTerm>)>, MetamorphicError> {
+        let mut facts: Vec<(Tag, NodeIndex, Vec<GroundTerm>)> = Vec::new();
+        for tag in Self::eligible_rel_nodes(adg, transformation_type) {
+            for (fact_node, terms) in adg.get_fact_nodes(&tag)? {
+                facts.push((tag.clone(), fact_node, terms));
+            }
+        }
+        Ok(facts)
+    }
+
+    /// Remove the fact of a recorded step.
+    /// Returns None if there is no such fact (anymore) or
+    /// removing it does not fit the transformation type.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+        chosen_rel_node: Tag,
+        terms: Vec<GroundTerm>,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let chosen_fact_node: Option<NodeIndex> = Self::eligible_facts(adg, transformation_type)?
+            .into_iter()
+            .find(|(tag, _, fact_terms)| *tag == chosen_rel_node && same_terms(fact_terms, &terms))
+            .map(|(_, fact_node, _)| fact_node);
+        let Some(chosen_fact_node) = chosen_fact_node else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            chosen_rel_node,
+            chosen_fact_node,
+            terms,
+        }))
+    }
+
+    /// Whether the statement is the fact to be removed
+    fn is_chosen_fact(&self, fact: &Fact) -> bool {
+        if *fact.predicate() != self.chosen_rel_node {
+            return false;
+        }
+        fact_ground_terms(fact).is_some_and(|fact_terms| same_terms(&fact_terms, &self.terms))
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for RemoveFact<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some((chosen_rel_node, chosen_fact_node, terms)) =
+            Self::eligible_facts(adg, transformation_type)?
+                .choose(rng)
+                .cloned()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            chosen_rel_node,
+            chosen_fact_node,
+            terms,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_facts(adg, transformation_type)?.is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for RemoveFact<'a, 'b> {
+    fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        let mut commit: ProgramCommit = program.fork();
+
+        // Keep everything but the first occurrence of the fact
+        let mut removed: bool = false;
+        for statement in program.statements() {
+            match statement {
+                Statement::Fact(fact) if !removed && self.is_chosen_fact(fact) => removed = true,
+                _ => commit.keep(statement),
+            }
+        }
+
+        if removed {
+            if let Err(error) = self.adg.remove_fact_node(self.chosen_fact_node) {
+                self.adg.record_failed_update(format!(
+                    "Failed to update the ADG after removing a fact: {error}"
+                ));
+            }
+            let terms: Vec<String> = self.terms.iter().map(|gt| gt.to_string()).collect();
+            println!(
+                "Removed fact {}({})",
+                self.chosen_rel_node,
+                terms.join(", ")
+            );
+            self.adg.record_step(TransformationStep::RemoveFact {
+                chosen_rel_node: self.chosen_rel_node,
+                terms: self.terms,
+            });
+        } else {
+            println!(
+                "Fact node of {} has no fact in the program",
+                self.chosen_rel_node
+            );
+        }
+
+        commit.submit()
+    }
+}
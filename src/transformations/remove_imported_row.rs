@@ -0,0 +1,140 @@
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::commit::ProgramCommit;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::seq::IndexedRandom;
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+
+/// Remove a row from the loaded data of an imported file.
+/// The inverse of `AddImportedRow`:
+/// Oracle depends on ancestry of all relational nodes that import the file,
+/// like for `RemoveFact`.
+pub struct RemoveImportedRow<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    _rng: &'b mut rand_chacha::ChaCha8Rng,
+    resource: String,
+    row: Vec<String>,
+}
+
+impl<'a, 'b> RemoveImportedRow<'a, 'b> {
+    /// The relational nodes tuples can be removed from under the transformation type
+    fn eligible_rel_nodes(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<Tag> {
+        match transformation_type {
+            TransformationTypes::EQU => adg.get_none_ancestry_relational_nodes(),
+            TransformationTypes::CON => adg.get_leq_positive_ancestry_relational_nodes(),
+            TransformationTypes::EXP => adg.get_leq_negative_ancestry_relational_nodes(),
+        }
+    }
+
+    /// The rows that can be removed, with the resource they belong to
+    fn eligible_rows(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Vec<(String, Vec<String>)> {
+        let eligible_rel_nodes: Vec<Tag> = Self::eligible_rel_nodes(adg, transformation_type);
+        let mut rows: Vec<(String, Vec<String>)> = Vec::new();
+        for (resource, tags) in adg.get_imported_resources() {
+            if tags.is_empty() || !tags.iter().all(|tag| eligible_rel_nodes.contains(tag)) {
+                continue;
+            }
+            let Some(data) = adg.get_imported_data(&resource) else {
+                continue;
+            };
+            for row in data.rows() {
+                rows.push((resource.clone(), row.clone()));
+            }
+        }
+        rows
+    }
+
+    /// Remove the row of a recorded step.
+    /// Returns None if there is no such row (anymore) or
+    /// removing it does not fit the transformation type.
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+        resource: String,
+        row: Vec<String>,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let eligible: bool = Self::eligible_rows(adg, transformation_type)
+            .into_iter()
+            .any(|(eligible_resource, eligible_row)| {
+                eligible_resource == resource && eligible_row == row
+            });
+        if !eligible {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            resource,
+            row,
+        }))
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for RemoveImportedRow<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some((resource, row)) = Self::eligible_rows(adg, transformation_type)
+            .choose(rng)
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            resource,
+            row,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_rows(adg, transformation_type).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for RemoveImportedRow<'a, 'b> {
+    fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        // The program stays the same, only the data changes
+        let commit: ProgramCommit = program.fork_full();
+
+        match self.adg.remove_imported_row(&self.resource, &self.row) {
+            Ok(true) => {
+                println!(
+                    "Removed row ({}) from {}",
+                    self.row.join(", "),
+                    self.resource
+                );
+                self.adg.record_step(TransformationStep::RemoveImportedRow {
+                    resource: self.resource,
+                    row: self.row,
+                });
+            }
+            Ok(false) => println!("Row of {} has vanished from its data", self.resource),
+            Err(error) => self.adg.record_failed_update(format!(
+                "Failed to update the ADG after removing a row: {error}"
+            )),
+        }
+
+        commit.submit()
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use nemo::rule_model::components::IterableVariables;
+use nemo::rule_model::components::literal::Literal;
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::components::term::primitive::variable::Variable;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::seq::{IndexedRandom, SliceRandom};
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util;
+
+/// Permute the body literals of a rule and rename its variables to fresh names.
+/// The rule stays the same up to the order of its body and the names of its
+/// variables, so the program stays equivalent, but nemo joins the body
+/// in a different order and orders the variables differently.
+pub struct ReorderAndRenameRule<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    rng: &'b mut rand_chacha::ChaCha8Rng,
+    rule_name: String,
+    /// The body order and renaming when replaying a recorded step
+    planned: Option<(Vec<usize>, Vec<(String, String)>)>,
+}
+
+impl<'a, 'b> ReorderAndRenameRule<'a, 'b> {
+    /// Rewrite the rule of a recorded step.
+    /// Returns None if the ADG has no such rule (anymore).
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        rule_name: String,
+        body_order: Vec<usize>,
+        renaming: Vec<(String, String)>,
+    ) -> Option<Self> {
+        if !adg.get_rule_names(false).contains(&rule_name) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: Some((body_order, renaming)),
+        })
+    }
+
+    /// A random order of the body literals and a renaming of every named variable.
+    /// The new names share a prefix that no variable of the rule starts with.
+    fn plan_rewrite(&mut self, rule: &Rule) -> (Vec<usize>, Vec<(String, String)>) {
+        let mut body_order: Vec<usize> = (0..rule.body().len()).collect();
+        body_order.shuffle(self.rng);
+
+        let mut names: Vec<String> = Vec::new();
+        for variable in rule.variables() {
+            if let Some(name) = variable.name() {
+                if !names.iter().any(|known| known == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        let prefix: String = util::fresh_variable_prefix(rule);
+        // Fresh names in random order, so the variable order changes as well
+        let mut numbers: Vec<usize> = (0..names.len()).collect();
+        numbers.shuffle(self.rng);
+        let renaming: Vec<(String, String)> = names
+            .into_iter()
+            .zip(numbers)
+            .map(|(name, number)| (name, format!("{prefix}_{number}")))
+            .collect();
+        (body_order, renaming)
+    }
+}
+
+/// The rule with its body in the given order and its variables renamed.
+/// None if the order is not a permutation of the body.
+fn rewrite_rule(rule: &Rule, body_order: &[usize], renaming: &[(String, String)]) -> Option<Rule> {
+    let mut sorted_order: Vec<usize> = body_order.to_vec();
+    sorted_order.sort();
+    if sorted_order != (0..rule.body().len()).collect::<Vec<usize>>() {
+        return None;
+    }
+    let mut rewritten: Rule = rule.clone();
+    let body: Vec<Literal> = body_order
+        .iter()
+        .map(|index| rule.body()[*index].clone())
+        .collect();
+    *rewritten.body_mut() = body;
+
+    let renaming: HashMap<&str, &str> = renaming
+        .iter()
+        .map(|(name, new_name)| (name.as_str(), new_name.as_str()))
+        .collect();
+    for variable in rewritten.variables_mut() {
+        let Some(new_name) = variable.name().and_then(|name| renaming.get(name)) else {
+            continue;
+        };
+        let renamed: Variable = match variable.is_existential() {
+            true => Variable::existential(new_name),
+            false => Variable::universal(new_name),
+        };
+        *variable = renamed;
+    }
+    Some(rewritten)
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for ReorderAndRenameRule<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some(rule_name) = adg.get_rule_names(false).choose(rng).cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            rng,
+            rule_name,
+            planned: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!adg.get_rule_names(false).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for ReorderAndRenameRule<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), program) else {
+            println!("Rule {} has vanished from the program", self.rule_name);
+            return program.fork_full().submit();
+        };
+        let (body_order, renaming) = match self.planned.take() {
+            Some(planned) => planned,
+            None => self.plan_rewrite(rule),
+        };
+        let Some(rewritten) = rewrite_rule(rule, &body_order, &renaming) else {
+            println!(
+                "Body order {:?} does not fit rule {}",
+                body_order, self.rule_name
+            );
+            return program.fork_full().submit();
+        };
+
+        let new_program: ProgramHandle =
+            util::replace_rule(program, &self.rule_name, std::slice::from_ref(&rewritten))?;
+
+        // The rewritten rule has a new id and new terms
+        self.adg.remove_rule_edges(&self.rule_name);
+        if let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), &new_program) {
+            if let Err(error) = self.adg.add_rule_edges(rule) {
+                self.adg.record_failed_update(format!(
+                    "Failed to add the edges of a rewritten rule to the ADG: {error}"
+                ));
+            }
+        }
+        println!("Rewrote rule {} to {}", self.rule_name, rewritten);
+        self.adg
+            .record_step(TransformationStep::ReorderAndRenameRule {
+                rule_name: self.rule_name,
+                body_order,
+                renaming,
+            });
+
+        Ok(new_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::rule_model::components::{
+        atom::Atom, literal::Literal, rule::Rule, tag::Tag, term::Term,
+    };
+
+    use super::rewrite_rule;
+
+    fn atom(predicate: &str, terms: Vec<Term>) -> Atom {
+        Atom::new(Tag::new(predicate.to_string()), terms)
+    }
+
+    fn universal(name: &str) -> Term {
+        Term::universal_variable(name)
+    }
+
+    /// `p(?x, !v) :- e(?x, ?y), q(?y, ?x)`
+    fn existential_rule() -> Rule {
+        Rule::new(
+            vec![atom(
+                "p",
+                vec![universal("x"), Term::existential_variable("v")],
+            )],
+            vec![
+                Literal::Positive(atom("e", vec![universal("x"), universal("y")])),
+                Literal::Positive(atom("q", vec![universal("y"), universal("x")])),
+            ],
+        )
+    }
+
+    fn renaming() -> Vec<(String, String)> {
+        [("x", "r_0"), ("y", "r_1"), ("v", "r_2")]
+            .into_iter()
+            .map(|(name, new_name)| (name.to_string(), new_name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn body_order_must_be_a_permutation() {
+        let rule = existential_rule();
+        for body_order in [vec![0], vec![0, 0], vec![0, 2], vec![0, 1, 2]] {
+            assert!(rewrite_rule(&rule, &body_order, &renaming()).is_none());
+        }
+    }
+
+    #[test]
+    fn existential_variables_stay_existential() {
+        let rewritten = rewrite_rule(&existential_rule(), &[0, 1], &renaming())
+            .expect("[0, 1] is a permutation of the body");
+        assert_eq!(
+            rewritten.head(),
+            &vec![atom(
+                "p",
+                vec![universal("r_0"), Term::existential_variable("r_2")]
+            )]
+        );
+    }
+
+    #[test]
+    fn repeated_variables_are_renamed_consistently() {
+        let rewritten = rewrite_rule(&existential_rule(), &[1, 0], &renaming())
+            .expect("[1, 0] is a permutation of the body");
+        assert_eq!(
+            rewritten.body(),
+            &vec![
+                Literal::Positive(atom("q", vec![universal("r_1"), universal("r_0")])),
+                Literal::Positive(atom("e", vec![universal("r_0"), universal("r_1")])),
+            ]
+        );
+    }
+}
@@ -1,8 +1,7 @@
-use std::process::exit;
-
 use nemo::rule_model::components::import_export::ExportDirective;
 use nemo::rule_model::components::output::Output;
 use nemo::rule_model::components::statement::Statement;
+use nemo::rule_model::components::tag::Tag;
 use nemo::rule_model::error::ValidationReport;
 use nemo::rule_model::programs::handle::ProgramHandle;
 
@@ -10,6 +9,7 @@ use nemo::rule_model::pipeline::transformations::ProgramTransformation;
 use nemo::rule_model::programs::{ProgramRead, ProgramWrite};
 use rand::seq::IteratorRandom;
 
+use crate::error::MetamorphicError;
 use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGraph;
 
 /// Program transformation
@@ -17,40 +17,95 @@ use crate::transformations::annotated_dependency_graphs::AnnotatedDependencyGrap
 /// as the only output predicate. If none are available,
 /// select a random predicate from the idb predicates.
 // #[derive(Debug, Clone, Copy, Default)]
-pub struct TransformationSelectRandomOutputPredicate<'a,'b> {
+pub struct TransformationSelectRandomOutputPredicate<'a> {
     adg: &'a mut AnnotatedDependencyGraph,
-    rng: &'b mut rand_chacha::ChaCha8Rng,
+    /// The predicate to export
+    chosen_predicate: Tag,
 }
 
-impl<'a,'b> TransformationSelectRandomOutputPredicate<'a,'b> {
+impl<'a> TransformationSelectRandomOutputPredicate<'a> {
     /* fn fetch_adg(self) -> &'a mut AnnotatedDependencyGraph {
         self.adg
     } */
-    pub fn new(adg: &'a mut AnnotatedDependencyGraph, rng: &'b mut rand_chacha::ChaCha8Rng) -> Self {
-        Self { adg, rng }
+    /// Choose a random export/output predicate of the program, or
+    /// a random derived predicate if there are none.
+    pub fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &mut rand_chacha::ChaCha8Rng,
+        program: &ProgramHandle,
+    ) -> Result<Self, MetamorphicError> {
+        // Collect export & output predicates
+        let mut export_predicates: Vec<Tag> = Vec::new();
+        for statement in program.statements() {
+            match statement {
+                Statement::Export(export) => {
+                    export_predicates.push(export.predicate().clone());
+                    println!("Found export: {}", export.predicate());
+                }
+                Statement::Output(output) => {
+                    export_predicates.push(output.predicate().clone());
+                    println!("Found output: {}", output.predicate());
+                }
+                _ => (),
+            }
+        }
+
+        let chosen_predicate: Tag = if export_predicates.is_empty() {
+            // If there are none, choose one of the derived randomly.
+            // Sort them, so the choice only depends on the seed
+            let mut der_pred: Vec<Tag> = program.derived_predicates().into_iter().collect();
+            der_pred.sort_by(|a, b| a.name().cmp(b.name()));
+            let num_derived_predicates = der_pred.len();
+            match der_pred.into_iter().choose(rng) {
+                None => return Err(MetamorphicError::NoDerivedPredicates),
+                Some(tag) => {
+                    println!(
+                        "Using the randomly chosen derived predicate of {num_derived_predicates}: {}",
+                        tag.name()
+                    );
+                    tag
+                }
+            }
+        } else {
+            // If there are some, choose a random export predicate
+            let num_export_predicates = export_predicates.len();
+            match export_predicates.into_iter().choose(rng) {
+                None => return Err(MetamorphicError::NoDerivedPredicates),
+                Some(tag) => {
+                    println!(
+                        "Using the randomly chosen export predicate of {num_export_predicates}: {}",
+                        tag.name()
+                    );
+                    tag
+                }
+            }
+        };
+        Ok(Self {
+            adg,
+            chosen_predicate,
+        })
+    }
+    /// Export the given predicate instead of a random one
+    pub fn with_predicate(adg: &'a mut AnnotatedDependencyGraph, predicate: Tag) -> Self {
+        println!("Using the given predicate: {}", predicate.name());
+        Self {
+            adg,
+            chosen_predicate: predicate,
+        }
     }
 }
 
-impl<'a,'b> ProgramTransformation for TransformationSelectRandomOutputPredicate<'a,'b> {
+impl<'a> ProgramTransformation for TransformationSelectRandomOutputPredicate<'a> {
     fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
         println!("Choosing a predicate to export!");
 
         let mut commit = program.fork();
 
-        // Collect export & output statements
-        let mut export_directives: Vec<&Statement> = Vec::new();
         // Keep all other than original export statements
         program
             .statements()
             .for_each(|s| match s {
-                Statement::Export(export) => {
-                    export_directives.push(s);
-                    println!("Found export: {}", export.predicate());
-                }
-                Statement::Output(output) => {
-                    export_directives.push(s);
-                    println!("Found output: {}", output.predicate());
-                }
+                Statement::Export(_) | Statement::Output(_) => (),
                 /* Statement::Rule(rule)=>{
                     println!("Name: {:?}",rule.name());
                     commit.keep(s);
@@ -79,54 +134,9 @@ impl<'a,'b> ProgramTransformation for TransformationSelectRandomOutputPredicate<
         }
 
         // Add export statement
-        if export_directives.len() == 0 {
-            // If there are none, choose one of the derived randomly
-            let der_pred = program.derived_predicates();
-            let num_derived_predicates = der_pred.len();
-            let chosen = der_pred.iter().choose(self.rng);
-            match chosen {
-                Some(tag) => {
-                    println!(
-                        "Using the randomly chosen derived predicate of {num_derived_predicates}: {}",
-                        tag.name()
-                    );
-                    let export = ExportDirective::new_csv(tag.clone());
-                    self.adg.set_output_rel(&export.predicate());
-                    commit.add_export(export);
-                }
-                None => {
-                    println!("No predicates derived");
-                    exit(1);
-                }
-            }
-        } else {
-            // If there are some, choose a random export predicate
-            let num_export_predicates = export_directives.len();
-            let chosen = export_directives.iter().choose(self.rng);
-            match chosen {
-                Some(tag) => {
-                    let predicate_name = match tag {
-                        Statement::Export(export) => export.predicate(),
-                        Statement::Output(output) => output.predicate(),
-                        _ => {
-                            println!("Export directive was not an export directive");
-                            exit(1);
-                        }
-                    };
-                    println!(
-                        "Using the randomly chosen export predicate of {num_export_predicates}: {}",
-                        predicate_name.name()
-                    );
-                    let export = ExportDirective::new_csv(predicate_name.clone());
-                    self.adg.set_output_rel(&export.predicate());
-                    commit.add_export(export);
-                }
-                None => {
-                    println!("No predicates derived");
-                    exit(1);
-                }
-            }
-        }
+        let export = ExportDirective::new_csv(self.chosen_predicate);
+        self.adg.set_output_rel(&export.predicate());
+        commit.add_export(export);
         commit.submit()
     }
 }
@@ -0,0 +1,92 @@
+//! Serde helpers for nemo's rule model types, to be used with `#[serde(with = "...")]`
+
+use nemo::{
+    datavalues::{AnyDataValue, DataValue, ValueDomain},
+    rule_model::components::{tag::Tag, term::primitive::ground::GroundTerm},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A ground term as it is stored in JSON files
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SerializedGroundTerm {
+    Iri { iri: String },
+    Literal { lexical: String, datatype: String },
+}
+impl SerializedGroundTerm {
+    pub fn new(ground_term: &GroundTerm) -> Self {
+        let value: AnyDataValue = ground_term.value();
+        match value.value_domain() {
+            ValueDomain::Iri => Self::Iri {
+                iri: value.lexical_value(),
+            },
+            _ => Self::Literal {
+                lexical: value.lexical_value(),
+                datatype: value.datatype_iri(),
+            },
+        }
+    }
+
+    pub fn to_ground_term(&self) -> Result<GroundTerm, String> {
+        match self {
+            Self::Iri { iri } => Ok(GroundTerm::new(AnyDataValue::new_iri(iri.clone()))),
+            Self::Literal { lexical, datatype } => {
+                match AnyDataValue::new_from_typed_literal(lexical.clone(), datatype.clone()) {
+                    Ok(value) => Ok(GroundTerm::new(value)),
+                    Err(error) => Err(format!(
+                        "Invalid literal \"{lexical}\"^^<{datatype}>: {error}"
+                    )),
+                }
+            }
+        }
+    }
+}
+
+pub mod tag {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(tag: &Tag, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(tag.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tag, D::Error> {
+        Ok(Tag::new(String::deserialize(deserializer)?))
+    }
+}
+
+pub mod ground_term {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        ground_term: &GroundTerm,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SerializedGroundTerm::new(ground_term).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<GroundTerm, D::Error> {
+        SerializedGroundTerm::deserialize(deserializer)?
+            .to_ground_term()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+pub mod ground_terms {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        ground_terms: &[GroundTerm],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(ground_terms.iter().map(SerializedGroundTerm::new))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<GroundTerm>, D::Error> {
+        Vec::<SerializedGroundTerm>::deserialize(deserializer)?
+            .iter()
+            .map(|gt| gt.to_ground_term().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
@@ -1,15 +1,20 @@
-use std::process::exit;
+use std::{fmt::Display, str::FromStr};
 
 use nemo::rule_model::{
     error::ValidationReport, pipeline::transformations::ProgramTransformation,
     programs::handle::ProgramHandle,
 };
-use rand::Rng;
+use rand::seq::IndexedRandom;
 
+use crate::error::MetamorphicError;
 use crate::transformations::{
     MetamorphicTransformation, add_fact_node_and_edge::AddFactNodeAndEdge,
-    add_relational_node::AddRelationalNode, annotated_dependency_graphs::AnnotatedDependencyGraph,
-    transformation_types::TransformationTypes,
+    add_imported_row::AddImportedRow, add_redundant_body_atom::AddRedundantBodyAtom,
+    add_relational_node::AddRelationalNode, add_rule_edge::AddRuleEdge,
+    annotated_dependency_graphs::AnnotatedDependencyGraph, duplicate_rule::DuplicateRule,
+    fold_predicate::FoldPredicate, remove_fact::RemoveFact, remove_imported_row::RemoveImportedRow,
+    reorder_and_rename_rule::ReorderAndRenameRule, transformation_types::TransformationTypes,
+    unfold_rule::UnfoldRule,
 };
 
 pub struct TransformationManager<'a, 'b> {
@@ -61,125 +66,305 @@ impl<'a, 'b> TransformationManager<'a, 'b> {
     }
 } */
 
-pub struct IterateMetamorphicTransformations<'a, 'b> {
-    adg: Option<&'a mut AnnotatedDependencyGraph>,
-    rng: Option<&'b mut rand_chacha::ChaCha8Rng>,
-    transformation_type: Option<TransformationTypes>,
+/// The kinds of metamorphic transformations the manager chooses from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransformationKind {
+    AddRelationalNode,
+    AddFactNodeAndEdge,
+    AddRuleEdge,
+    RemoveFact,
+    AddImportedRow,
+    RemoveImportedRow,
+    DuplicateRule,
+    ReorderAndRenameRule,
+    AddRedundantBodyAtom,
+    UnfoldRule,
+    FoldPredicate,
 }
-impl<'a, 'b> IterateMetamorphicTransformations<'a, 'b> {
-    pub fn new(
-        adg: &'a mut AnnotatedDependencyGraph,
-        rng: &'b mut rand_chacha::ChaCha8Rng,
+impl TransformationKind {
+    pub const ALL: [TransformationKind; 11] = [
+        TransformationKind::AddRelationalNode,
+        TransformationKind::AddFactNodeAndEdge,
+        TransformationKind::AddRuleEdge,
+        TransformationKind::RemoveFact,
+        TransformationKind::AddImportedRow,
+        TransformationKind::RemoveImportedRow,
+        TransformationKind::DuplicateRule,
+        TransformationKind::ReorderAndRenameRule,
+        TransformationKind::AddRedundantBodyAtom,
+        TransformationKind::UnfoldRule,
+        TransformationKind::FoldPredicate,
+    ];
+
+    /// Whether a transformation of this kind can currently be applied
+    fn is_applicable(
+        self,
+        adg: &AnnotatedDependencyGraph,
         transformation_type: TransformationTypes,
-    ) -> IterateMetamorphicTransformations<'a, 'b> {
-        IterateMetamorphicTransformations {
-            adg: Some(adg),
-            rng: Some(rng),
-            transformation_type: Some(transformation_type),
+    ) -> Result<bool, MetamorphicError> {
+        match self {
+            TransformationKind::AddRelationalNode => {
+                AddRelationalNode::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::AddFactNodeAndEdge => {
+                AddFactNodeAndEdge::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::AddRuleEdge => AddRuleEdge::is_applicable(adg, transformation_type),
+            TransformationKind::RemoveFact => RemoveFact::is_applicable(adg, transformation_type),
+            TransformationKind::AddImportedRow => {
+                AddImportedRow::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::RemoveImportedRow => {
+                RemoveImportedRow::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::DuplicateRule => {
+                DuplicateRule::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::ReorderAndRenameRule => {
+                ReorderAndRenameRule::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::AddRedundantBodyAtom => {
+                AddRedundantBodyAtom::is_applicable(adg, transformation_type)
+            }
+            TransformationKind::UnfoldRule => UnfoldRule::is_applicable(adg, transformation_type),
+            TransformationKind::FoldPredicate => {
+                FoldPredicate::is_applicable(adg, transformation_type)
+            }
         }
     }
 }
-impl<'a, 'b> Iterator for IterateMetamorphicTransformations<'a, 'b> {
-    type Item = SomeMetamorphicTransformation<'a, 'b>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let adg = self.adg.take();
-        let rng = self.rng.take();
-        let transformation_type = self.transformation_type.take();
-        SomeMetamorphicTransformation::new_opt(adg, rng, transformation_type)
+impl Display for TransformationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformationKind::AddRelationalNode => f.write_str("add-relational-node"),
+            TransformationKind::AddFactNodeAndEdge => f.write_str("add-fact-node-and-edge"),
+            TransformationKind::AddRuleEdge => f.write_str("add-rule-edge"),
+            TransformationKind::RemoveFact => f.write_str("remove-fact"),
+            TransformationKind::AddImportedRow => f.write_str("add-imported-row"),
+            TransformationKind::RemoveImportedRow => f.write_str("remove-imported-row"),
+            TransformationKind::DuplicateRule => f.write_str("duplicate-rule"),
+            TransformationKind::ReorderAndRenameRule => f.write_str("reorder-and-rename-rule"),
+            TransformationKind::AddRedundantBodyAtom => f.write_str("add-redundant-body-atom"),
+            TransformationKind::UnfoldRule => f.write_str("unfold-rule"),
+            TransformationKind::FoldPredicate => f.write_str("fold-predicate"),
+        }
+    }
+}
+impl FromStr for TransformationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TransformationKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s.to_ascii_lowercase())
+            .ok_or_else(|| {
+                let kinds: Vec<String> = TransformationKind::ALL
+                    .iter()
+                    .map(|kind| kind.to_string())
+                    .collect();
+                format!(
+                    "Unknown transformation {s}, expected one of {}",
+                    kinds.join(", ")
+                )
+            })
+    }
+}
+
+/// Relative weights with which the applicable transformations are chosen.
+/// A transformation with weight 0 is never chosen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransformationWeights {
+    weights: Vec<(TransformationKind, u32)>,
+}
+impl TransformationWeights {
+    pub fn weight(&self, kind: TransformationKind) -> u32 {
+        self.weights
+            .iter()
+            .find(|(weighted_kind, _)| *weighted_kind == kind)
+            .map_or(0, |(_, weight)| *weight)
+    }
+    pub fn set_weight(&mut self, kind: TransformationKind, weight: u32) {
+        for (weighted_kind, old_weight) in self.weights.iter_mut() {
+            if *weighted_kind == kind {
+                *old_weight = weight;
+            }
+        }
+    }
+}
+impl Default for TransformationWeights {
+    /// All transformations are equally likely
+    fn default() -> Self {
+        Self {
+            weights: TransformationKind::ALL.map(|kind| (kind, 1)).to_vec(),
+        }
+    }
+}
+impl Display for TransformationWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let weights: Vec<String> = self
+            .weights
+            .iter()
+            .map(|(kind, weight)| format!("{kind}={weight}"))
+            .collect();
+        f.write_str(&weights.join(","))
+    }
+}
+impl FromStr for TransformationWeights {
+    type Err = String;
+
+    /// Parse `kind=weight` pairs separated by commas.
+    /// Transformations that are not mentioned keep weight 1.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = TransformationWeights::default();
+        for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let Some((kind, weight)) = pair.split_once('=') else {
+                return Err(format!("Expected kind=weight, got {pair}"));
+            };
+            let kind: TransformationKind = kind.trim().parse()?;
+            let weight: u32 = match weight.trim().parse() {
+                Ok(weight) => weight,
+                Err(_) => return Err(format!("Invalid weight {weight} of {kind}")),
+            };
+            weights.set_weight(kind, weight);
+        }
+        Ok(weights)
     }
 }
 
 pub enum SomeMetamorphicTransformation<'a, 'b> {
     AddRelationalNode(AddRelationalNode<'a, 'b>),
     AddFactNodeAndEdge(AddFactNodeAndEdge<'a, 'b>),
-    Default(),
+    AddRuleEdge(AddRuleEdge<'a, 'b>),
+    RemoveFact(RemoveFact<'a, 'b>),
+    AddImportedRow(AddImportedRow<'a, 'b>),
+    RemoveImportedRow(RemoveImportedRow<'a, 'b>),
+    DuplicateRule(DuplicateRule<'a, 'b>),
+    ReorderAndRenameRule(ReorderAndRenameRule<'a, 'b>),
+    AddRedundantBodyAtom(AddRedundantBodyAtom<'a, 'b>),
+    UnfoldRule(UnfoldRule<'a, 'b>),
+    FoldPredicate(FoldPredicate<'a, 'b>),
 }
 impl<'a, 'b> SomeMetamorphicTransformation<'a, 'b> {
-    fn new_opt(
-        adg: Option<&'a mut AnnotatedDependencyGraph>,
-        rng: Option<&'b mut rand_chacha::ChaCha8Rng>,
-        transformation_type: Option<TransformationTypes>,
-    ) -> Option<Self> {
-        if let Some(rng) = rng {
-            if let Some(adg) = adg {
-                if let Some(transformation_type) = transformation_type {
-                    match rng.random_range(0..NUM_TRANSFORMATION_TYPES) {
-                        0 => Some(Self::AddRelationalNode(AddRelationalNode::new(
-                            adg,
-                            rng,
-                            transformation_type,
-                        )?)),
-                        1 => Some(Self::AddFactNodeAndEdge(AddFactNodeAndEdge::new(
-                            adg,
-                            rng,
-                            transformation_type,
-                        )?)),
-                        _ => Some(Self::Default()),
-                    }
-                } else {
-                    println!(
-                        "Found None where Some expected in SomeMetamorphicTransformation new_opt"
-                    );
-                    exit(1);
-                }
-            } else {
-                println!("Found None where Some expected in SomeMetamorphicTransformation new_opt");
-                exit(1);
-            }
-        } else {
-            println!("Found None where Some expected in SomeMetamorphicTransformation new_opt");
-            exit(1);
+    /// The transformations with a positive weight that can currently be applied
+    pub fn applicable_kinds(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+        weights: &TransformationWeights,
+    ) -> Result<Vec<(TransformationKind, u32)>, MetamorphicError> {
+        let mut applicable: Vec<(TransformationKind, u32)> = Vec::new();
+        for kind in TransformationKind::ALL {
+            let weight: u32 = weights.weight(kind);
+            if weight > 0 && kind.is_applicable(adg, transformation_type.clone())? {
+                applicable.push((kind, weight));
+            }
         }
+        Ok(applicable)
     }
-}
-// ^^ add here
-static NUM_TRANSFORMATION_TYPES: i32 = 2;
-// vv and here
-impl<'a, 'b> MetamorphicTransformation<'a, 'b> for SomeMetamorphicTransformation<'a, 'b> {
-    fn new(
+
+    /// Choose one of the applicable transformations by weight.
+    /// Returns None if no transformation can be applied.
+    pub fn choose(
         adg: &'a mut AnnotatedDependencyGraph,
         rng: &'b mut rand_chacha::ChaCha8Rng,
         transformation_type: TransformationTypes,
-    ) -> Option<Self> {
-        match rng.random_range(0..NUM_TRANSFORMATION_TYPES) {
-            0 => Some(Self::AddRelationalNode(AddRelationalNode::new(
-                adg,
-                rng,
-                transformation_type,
-            )?)),
-            1 => Some(Self::AddFactNodeAndEdge(AddFactNodeAndEdge::new(
-                adg,
-                rng,
-                transformation_type,
-            )?)),
-            _ => Some(Self::Default()),
-        }
+        weights: &TransformationWeights,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let applicable = Self::applicable_kinds(adg, transformation_type.clone(), weights)?;
+        let kind: TransformationKind = match applicable.choose_weighted(rng, |(_, weight)| *weight)
+        {
+            Ok((kind, _)) => *kind,
+            Err(_) => return Ok(None),
+        };
+        Self::new_of_kind(kind, adg, rng, transformation_type)
     }
-    /* fn can_apply(self: Self, intended_transformation_type: TransformationTypes) -> (bool, Self)
-    where
-        Self: Sized,
-    {
-        match self {
-            Self::Default() => {
-                println!("Cannot check default case of SomeMetamorphicTransformation");
-                exit(1);
+
+    /// Construct a transformation of the given kind
+    fn new_of_kind(
+        kind: TransformationKind,
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        match kind {
+            TransformationKind::AddRelationalNode => {
+                Ok(AddRelationalNode::new(adg, rng, transformation_type)?
+                    .map(Self::AddRelationalNode))
+            }
+            TransformationKind::AddFactNodeAndEdge => {
+                Ok(AddFactNodeAndEdge::new(adg, rng, transformation_type)?
+                    .map(Self::AddFactNodeAndEdge))
+            }
+            TransformationKind::AddRuleEdge => {
+                Ok(AddRuleEdge::new(adg, rng, transformation_type)?.map(Self::AddRuleEdge))
+            }
+            TransformationKind::RemoveFact => {
+                Ok(RemoveFact::new(adg, rng, transformation_type)?.map(Self::RemoveFact))
+            }
+            TransformationKind::AddImportedRow => {
+                Ok(AddImportedRow::new(adg, rng, transformation_type)?.map(Self::AddImportedRow))
+            }
+            TransformationKind::RemoveImportedRow => {
+                Ok(RemoveImportedRow::new(adg, rng, transformation_type)?
+                    .map(Self::RemoveImportedRow))
+            }
+            TransformationKind::DuplicateRule => {
+                Ok(DuplicateRule::new(adg, rng, transformation_type)?.map(Self::DuplicateRule))
             }
-            Self::AddRelationalNode(t) => {
-                let (tf, t) = t.can_apply(intended_transformation_type);
-                (tf, Self::AddRelationalNode(t))
+            TransformationKind::ReorderAndRenameRule => {
+                Ok(ReorderAndRenameRule::new(adg, rng, transformation_type)?
+                    .map(Self::ReorderAndRenameRule))
+            }
+            TransformationKind::AddRedundantBodyAtom => {
+                Ok(AddRedundantBodyAtom::new(adg, rng, transformation_type)?
+                    .map(Self::AddRedundantBodyAtom))
+            }
+            TransformationKind::UnfoldRule => {
+                Ok(UnfoldRule::new(adg, rng, transformation_type)?.map(Self::UnfoldRule))
+            }
+            TransformationKind::FoldPredicate => {
+                Ok(FoldPredicate::new(adg, rng, transformation_type)?.map(Self::FoldPredicate))
             }
         }
-    } */
+    }
+}
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for SomeMetamorphicTransformation<'a, 'b> {
+    /// Choose among the applicable transformations with equal weights
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        Self::choose(
+            adg,
+            rng,
+            transformation_type,
+            &TransformationWeights::default(),
+        )
+    }
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(
+            !Self::applicable_kinds(adg, transformation_type, &TransformationWeights::default())?
+                .is_empty(),
+        )
+    }
 }
 impl<'a, 'b> ProgramTransformation for SomeMetamorphicTransformation<'a, 'b> {
     fn apply(self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
         match self {
-            Self::Default() => {
-                println!("Cannot apply default case of SomeMetamorphicTransformation");
-                exit(1);
-            }
             Self::AddRelationalNode(t) => t.apply(program),
             Self::AddFactNodeAndEdge(t) => t.apply(program),
+            Self::AddRuleEdge(t) => t.apply(program),
+            Self::RemoveFact(t) => t.apply(program),
+            Self::AddImportedRow(t) => t.apply(program),
+            Self::RemoveImportedRow(t) => t.apply(program),
+            Self::DuplicateRule(t) => t.apply(program),
+            Self::ReorderAndRenameRule(t) => t.apply(program),
+            Self::AddRedundantBodyAtom(t) => t.apply(program),
+            Self::UnfoldRule(t) => t.apply(program),
+            Self::FoldPredicate(t) => t.apply(program),
         }
     }
 }
@@ -0,0 +1,283 @@
+use std::fmt::{Debug, Formatter};
+
+use nemo::rule_model::components::{tag::Tag, term::primitive::ground::GroundTerm};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MetamorphicError;
+use crate::transformations::{
+    add_fact_node_and_edge::AddFactNodeAndEdge,
+    add_imported_row::AddImportedRow,
+    add_redundant_body_atom::{AddRedundantBodyAtom, RedundantAtom},
+    add_relational_node::AddRelationalNode,
+    add_rule_edge::{AddRuleEdge, PlannedRule},
+    annotated_dependency_graphs::AnnotatedDependencyGraph,
+    duplicate_rule::DuplicateRule,
+    fold_predicate::{FoldPredicate, Folding},
+    remove_fact::RemoveFact,
+    remove_imported_row::RemoveImportedRow,
+    reorder_and_rename_rule::ReorderAndRenameRule,
+    serialization,
+    transformation_manager::SomeMetamorphicTransformation,
+    transformation_types::TransformationTypes,
+    unfold_rule::UnfoldRule,
+};
+
+/// A transformation that was applied to the program,
+/// together with all of the choices it made.
+/// Replaying a step does not draw random numbers.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum TransformationStep {
+    AddRelationalNode {
+        #[serde(with = "serialization::tag")]
+        relation: Tag,
+    },
+    AddFactNodeAndEdge {
+        #[serde(with = "serialization::tag")]
+        chosen_to_rel_node: Tag,
+        #[serde(with = "serialization::ground_terms")]
+        terms: Vec<GroundTerm>,
+    },
+    AddRuleEdge {
+        rule: PlannedRule,
+    },
+    RemoveFact {
+        #[serde(with = "serialization::tag")]
+        chosen_rel_node: Tag,
+        #[serde(with = "serialization::ground_terms")]
+        terms: Vec<GroundTerm>,
+    },
+    AddImportedRow {
+        resource: String,
+        row: Vec<String>,
+    },
+    RemoveImportedRow {
+        resource: String,
+        row: Vec<String>,
+    },
+    DuplicateRule {
+        rule_name: String,
+        copy_name: String,
+    },
+    ReorderAndRenameRule {
+        rule_name: String,
+        /// The new body lists the old body literals in this order
+        body_order: Vec<usize>,
+        /// Old and new name of every renamed variable
+        renaming: Vec<(String, String)>,
+    },
+    AddRedundantBodyAtom {
+        rule_name: String,
+        redundant_atom: RedundantAtom,
+    },
+    UnfoldRule {
+        rule_name: String,
+        /// Position of the unfolded atom among the positive body atoms
+        body_index: usize,
+        copy_names: Vec<String>,
+    },
+    FoldPredicate {
+        rule_name: String,
+        folding: Folding,
+    },
+}
+impl Debug for TransformationStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::AddRelationalNode { relation } => {
+                f.write_fmt(format_args!("AddRelationalNode {}", relation))
+            }
+            Self::AddFactNodeAndEdge {
+                chosen_to_rel_node,
+                terms,
+            } => {
+                let terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+                f.write_fmt(format_args!(
+                    "AddFactNodeAndEdge {}({})",
+                    chosen_to_rel_node,
+                    terms.join(", ")
+                ))
+            }
+            Self::AddRuleEdge { rule } => f.write_fmt(format_args!("AddRuleEdge {}", rule)),
+            Self::RemoveFact {
+                chosen_rel_node,
+                terms,
+            } => {
+                let terms: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+                f.write_fmt(format_args!(
+                    "RemoveFact {}({})",
+                    chosen_rel_node,
+                    terms.join(", ")
+                ))
+            }
+            Self::AddImportedRow { resource, row } => f.write_fmt(format_args!(
+                "AddImportedRow {} ({})",
+                resource,
+                row.join(", ")
+            )),
+            Self::RemoveImportedRow { resource, row } => f.write_fmt(format_args!(
+                "RemoveImportedRow {} ({})",
+                resource,
+                row.join(", ")
+            )),
+            Self::DuplicateRule {
+                rule_name,
+                copy_name,
+            } => f.write_fmt(format_args!("DuplicateRule {} to {}", rule_name, copy_name)),
+            Self::ReorderAndRenameRule {
+                rule_name,
+                body_order,
+                renaming,
+            } => {
+                let renaming: Vec<String> = renaming
+                    .iter()
+                    .map(|(name, new_name)| format!("{name} -> {new_name}"))
+                    .collect();
+                f.write_fmt(format_args!(
+                    "ReorderAndRenameRule {} body order {:?}, {}",
+                    rule_name,
+                    body_order,
+                    renaming.join(", ")
+                ))
+            }
+            Self::AddRedundantBodyAtom {
+                rule_name,
+                redundant_atom,
+            } => f.write_fmt(format_args!(
+                "AddRedundantBodyAtom {} {:?}",
+                rule_name, redundant_atom
+            )),
+            Self::UnfoldRule {
+                rule_name,
+                body_index,
+                copy_names,
+            } => f.write_fmt(format_args!(
+                "UnfoldRule {} at body atom {} into {}",
+                rule_name,
+                body_index,
+                copy_names.join(", ")
+            )),
+            Self::FoldPredicate { rule_name, folding } => {
+                f.write_fmt(format_args!("FoldPredicate {} {:?}", rule_name, folding))
+            }
+        }
+    }
+}
+impl TransformationStep {
+    /// Whether the step changes the data of an import instead of the program
+    pub fn changes_imported_data(&self) -> bool {
+        matches!(
+            self,
+            Self::AddImportedRow { .. } | Self::RemoveImportedRow { .. }
+        )
+    }
+
+    /// Rebuild the transformation of this step on the given ADG.
+    /// Returns None if the step can't be applied to the ADG under
+    /// the intended transformation type, e.g. because an earlier step is missing.
+    pub fn replay<'a, 'b>(
+        &self,
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut ChaCha8Rng,
+        transformation_type: TransformationTypes,
+    ) -> Result<Option<SomeMetamorphicTransformation<'a, 'b>>, MetamorphicError> {
+        match self {
+            Self::AddRelationalNode { relation } => {
+                Ok(AddRelationalNode::replay(adg, rng, relation.clone())
+                    .map(SomeMetamorphicTransformation::AddRelationalNode))
+            }
+            Self::AddFactNodeAndEdge {
+                chosen_to_rel_node,
+                terms,
+            } => Ok(AddFactNodeAndEdge::replay(
+                adg,
+                rng,
+                transformation_type,
+                chosen_to_rel_node.clone(),
+                terms.clone(),
+            )?
+            .map(SomeMetamorphicTransformation::AddFactNodeAndEdge)),
+            Self::AddRuleEdge { rule } => {
+                Ok(
+                    AddRuleEdge::replay(adg, rng, transformation_type, rule.clone())?
+                        .map(SomeMetamorphicTransformation::AddRuleEdge),
+                )
+            }
+            Self::RemoveFact {
+                chosen_rel_node,
+                terms,
+            } => Ok(RemoveFact::replay(
+                adg,
+                rng,
+                transformation_type,
+                chosen_rel_node.clone(),
+                terms.clone(),
+            )?
+            .map(SomeMetamorphicTransformation::RemoveFact)),
+            Self::AddImportedRow { resource, row } => Ok(AddImportedRow::replay(
+                adg,
+                rng,
+                transformation_type,
+                resource.clone(),
+                row.clone(),
+            )?
+            .map(SomeMetamorphicTransformation::AddImportedRow)),
+            Self::RemoveImportedRow { resource, row } => Ok(RemoveImportedRow::replay(
+                adg,
+                rng,
+                transformation_type,
+                resource.clone(),
+                row.clone(),
+            )?
+            .map(SomeMetamorphicTransformation::RemoveImportedRow)),
+            Self::DuplicateRule {
+                rule_name,
+                copy_name,
+            } => Ok(
+                DuplicateRule::replay(adg, rng, rule_name.clone(), copy_name.clone())
+                    .map(SomeMetamorphicTransformation::DuplicateRule),
+            ),
+            Self::ReorderAndRenameRule {
+                rule_name,
+                body_order,
+                renaming,
+            } => Ok(ReorderAndRenameRule::replay(
+                adg,
+                rng,
+                rule_name.clone(),
+                body_order.clone(),
+                renaming.clone(),
+            )
+            .map(SomeMetamorphicTransformation::ReorderAndRenameRule)),
+            Self::AddRedundantBodyAtom {
+                rule_name,
+                redundant_atom,
+            } => Ok(AddRedundantBodyAtom::replay(
+                adg,
+                rng,
+                rule_name.clone(),
+                redundant_atom.clone(),
+            )
+            .map(SomeMetamorphicTransformation::AddRedundantBodyAtom)),
+            Self::UnfoldRule {
+                rule_name,
+                body_index,
+                copy_names,
+            } => Ok(UnfoldRule::replay(
+                adg,
+                rng,
+                rule_name.clone(),
+                *body_index,
+                copy_names.clone(),
+            )
+            .map(SomeMetamorphicTransformation::UnfoldRule)),
+            Self::FoldPredicate { rule_name, folding } => {
+                Ok(
+                    FoldPredicate::replay(adg, rng, rule_name.clone(), folding.clone())
+                        .map(SomeMetamorphicTransformation::FoldPredicate),
+                )
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum TransformationTypes {
     EQU,
     EXP,
@@ -25,4 +27,25 @@ impl PartialOrd for TransformationTypes {
             (_,_) => None
         }
     }
-}
\ No newline at end of file
+}
+impl Display for TransformationTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformationTypes::EQU => f.write_str("EQU"),
+            TransformationTypes::EXP => f.write_str("EXP"),
+            TransformationTypes::CON => f.write_str("CON"),
+        }
+    }
+}
+impl FromStr for TransformationTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "EQU" => Ok(TransformationTypes::EQU),
+            "EXP" => Ok(TransformationTypes::EXP),
+            "CON" => Ok(TransformationTypes::CON),
+            _ => Err(format!("Unknown transformation type {s}, expected one of equ, exp, con")),
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::{HashMap, HashSet};
+
+use nemo::rule_model::components::IterableVariables;
+use nemo::rule_model::components::atom::Atom;
+use nemo::rule_model::components::literal::Literal;
+use nemo::rule_model::components::rule::Rule;
+use nemo::rule_model::components::tag::Tag;
+use nemo::rule_model::components::term::Term;
+use nemo::rule_model::components::term::primitive::Primitive;
+use nemo::rule_model::components::term::primitive::ground::GroundTerm;
+use nemo::rule_model::components::term::primitive::variable::Variable;
+use nemo::rule_model::error::ValidationReport;
+use nemo::rule_model::pipeline::transformations::ProgramTransformation;
+use nemo::rule_model::programs::ProgramRead;
+use nemo::rule_model::programs::handle::ProgramHandle;
+
+use rand::seq::IndexedRandom;
+
+use crate::error::MetamorphicError;
+use crate::transformations::MetamorphicTransformation;
+use crate::transformations::annotated_dependency_graphs::{AnnotatedDependencyGraph, Sign};
+use crate::transformations::transformation_step::TransformationStep;
+use crate::transformations::transformation_types::TransformationTypes;
+use crate::transformations::util;
+
+/// Unfold a positive body atom `q(...)` of a rule: replace the rule by one copy for
+/// every head atom over `q` of the rules defining `q`, with the body of that rule
+/// in place of the atom. The variables of the defining rule are renamed apart and
+/// unified with the atom, so the copies derive exactly what the rule derived.
+/// `q` must be defined only by non-recursive rules without existential variables or
+/// aggregates in their `q` atoms, and have no facts or imports.
+/// The unfolded rule must not have existential variables or aggregates either.
+/// The edges of the rule are replaced by direct edges from the bodies of the defining rules.
+pub struct UnfoldRule<'a, 'b> {
+    adg: &'a mut AnnotatedDependencyGraph,
+    _rng: &'b mut rand_chacha::ChaCha8Rng,
+    rule_name: String,
+    /// Position of the unfolded atom among the positive body atoms
+    body_index: usize,
+    /// The names of the copies when replaying a recorded step
+    planned_copy_names: Option<Vec<String>>,
+}
+
+/// Why a rule can't be unfolded at an atom
+#[derive(Debug)]
+enum UnfoldingError {
+    /// No head atom of a defining rule unifies with the atom
+    NoMatch,
+    /// A term is neither a variable nor a ground term, or a variable that
+    /// would be replaced by a ground term occurs in a complex term
+    UnsupportedTerm,
+}
+
+/// Most general unifier of variables and ground terms.
+/// Variables of the unfolded rule are kept in favour of the renamed variables
+/// of the defining rule, which start with the fresh prefix.
+struct Unifier {
+    fresh_prefix: String,
+    parent: HashMap<String, String>,
+    ground: HashMap<String, GroundTerm>,
+}
+impl Unifier {
+    fn new(fresh_prefix: String) -> Self {
+        Self {
+            fresh_prefix,
+            parent: HashMap::new(),
+            ground: HashMap::new(),
+        }
+    }
+
+    fn find(&self, variable: &str) -> String {
+        let mut root: &str = variable;
+        while let Some(parent) = self.parent.get(root) {
+            root = parent;
+        }
+        root.to_string()
+    }
+
+    /// Bind the variable to a ground term, false on a clash
+    fn bind(&mut self, variable: &str, value: &GroundTerm) -> bool {
+        let root: String = self.find(variable);
+        match self.ground.get(&root) {
+            Some(bound) => bound.value() == value.value(),
+            None => {
+                self.ground.insert(root, value.clone());
+                true
+            }
+        }
+    }
+
+    /// Merge the classes of both variables, false on a clash
+    fn union(&mut self, variable: &str, other: &str) -> bool {
+        let (mut root, mut other_root) = (self.find(variable), self.find(other));
+        if root == other_root {
+            return true;
+        }
+        if root.starts_with(&format!("{}_", self.fresh_prefix)) {
+            std::mem::swap(&mut root, &mut other_root);
+        }
+        self.parent.insert(other_root.clone(), root.clone());
+        match self.ground.remove(&other_root) {
+            Some(value) => self.bind(&root, &value),
+            None => true,
+        }
+    }
+
+    /// Unify two terms, false on a clash
+    fn unify(&mut self, term: &Term, other: &Term) -> Result<bool, UnfoldingError> {
+        let (Term::Primitive(primitive), Term::Primitive(other_primitive)) = (term, other) else {
+            return Err(UnfoldingError::UnsupportedTerm);
+        };
+        Ok(match (primitive, other_primitive) {
+            (Primitive::Variable(variable), Primitive::Variable(other_variable)) => {
+                match (variable.name(), other_variable.name()) {
+                    (Some(name), Some(other_name)) => self.union(name, other_name),
+                    // Anonymous variables match anything
+                    _ => true,
+                }
+            }
+            (Primitive::Variable(variable), Primitive::Ground(value))
+            | (Primitive::Ground(value), Primitive::Variable(variable)) => match variable.name() {
+                Some(name) => self.bind(name, value),
+                None => true,
+            },
+            (Primitive::Ground(value), Primitive::Ground(other_value)) => {
+                value.value() == other_value.value()
+            }
+        })
+    }
+}
+
+/// The rule with every named variable renamed to `<prefix>_<number>`
+fn rename_apart(rule: &Rule, prefix: &str) -> Rule {
+    let mut renaming: HashMap<String, String> = HashMap::new();
+    for variable in rule.variables() {
+        if let Some(name) = variable.name() {
+            let number: usize = renaming.len();
+            renaming
+                .entry(name.to_string())
+                .or_insert_with(|| format!("{prefix}_{number}"));
+        }
+    }
+    let mut renamed: Rule = rule.clone();
+    for variable in renamed.variables_mut() {
+        let Some(new_name) = variable.name().and_then(|name| renaming.get(name)) else {
+            continue;
+        };
+        let new_variable: Variable = match variable.is_existential() {
+            true => Variable::existential(new_name),
+            false => Variable::universal(new_name),
+        };
+        *variable = new_variable;
+    }
+    renamed
+}
+
+/// Replace the top level arguments of the atom that are bound to ground terms
+fn substitute_ground(atom: &mut Atom, unifier: &Unifier) {
+    for term in atom.terms_mut() {
+        let Term::Primitive(Primitive::Variable(variable)) = term else {
+            continue;
+        };
+        let Some(name) = variable.name() else {
+            continue;
+        };
+        if let Some(value) = unifier.ground.get(&unifier.find(name)) {
+            *term = Term::Primitive(Primitive::Ground(value.clone()));
+        }
+    }
+}
+
+/// Whether one of the variables occurs in a complex term of the atom
+fn nested_in_atom(atom: &Atom, variables: &HashSet<String>) -> bool {
+    atom.terms()
+        .filter(|term| !matches!(term, Term::Primitive(_)))
+        .any(|term| {
+            term.variables()
+                .any(|variable| variable.name().is_some_and(|name| variables.contains(name)))
+        })
+}
+
+/// Apply the unifier to the rule, which already contains the renamed body of the defining rule
+fn apply_unifier(mut rule: Rule, unifier: &Unifier) -> Result<Rule, UnfoldingError> {
+    let mut bound_to_ground: HashSet<String> = HashSet::new();
+    let mut renaming: HashMap<String, String> = HashMap::new();
+    for variable in rule.variables() {
+        let Some(name) = variable.name() else {
+            continue;
+        };
+        let root: String = unifier.find(name);
+        if unifier.ground.contains_key(&root) {
+            bound_to_ground.insert(name.to_string());
+        } else if root != name {
+            renaming.insert(name.to_string(), root);
+        }
+    }
+
+    // Ground terms can only replace arguments of atoms
+    let nested: bool = rule
+        .head()
+        .iter()
+        .any(|atom| nested_in_atom(atom, &bound_to_ground))
+        || rule.body().iter().any(|literal| match literal {
+            Literal::Positive(atom) | Literal::Negative(atom) => {
+                nested_in_atom(atom, &bound_to_ground)
+            }
+            Literal::Operation(operation) => operation.variables().any(|variable| {
+                variable
+                    .name()
+                    .is_some_and(|name| bound_to_ground.contains(name))
+            }),
+        });
+    if nested {
+        return Err(UnfoldingError::UnsupportedTerm);
+    }
+    for atom in rule.head_mut().iter_mut() {
+        substitute_ground(atom, unifier);
+    }
+    for literal in rule.body_mut().iter_mut() {
+        if let Literal::Positive(atom) | Literal::Negative(atom) = literal {
+            substitute_ground(atom, unifier);
+        }
+    }
+
+    for variable in rule.variables_mut() {
+        let Some(new_name) = variable.name().and_then(|name| renaming.get(name)) else {
+            continue;
+        };
+        let new_variable: Variable = match variable.is_existential() {
+            true => Variable::existential(new_name),
+            false => Variable::universal(new_name),
+        };
+        *variable = new_variable;
+    }
+    Ok(rule)
+}
+
+/// The copies of the rule with the positive body atom at `body_index` unfolded,
+/// in the order of the rules of the program, which are searched for the defining rules
+fn unfold<'r>(
+    rule: &Rule,
+    body_index: usize,
+    program_rules: impl Iterator<Item = &'r Rule>,
+) -> Result<Vec<Rule>, UnfoldingError> {
+    let Some(literal_index) = (0..rule.body().len())
+        .filter(|index| matches!(rule.body()[*index], Literal::Positive(_)))
+        .nth(body_index)
+    else {
+        return Err(UnfoldingError::NoMatch);
+    };
+    let Literal::Positive(atom) = &rule.body()[literal_index] else {
+        return Err(UnfoldingError::NoMatch);
+    };
+    let predicate: Tag = atom.predicate();
+    let fresh_prefix: String = util::fresh_variable_prefix(rule);
+
+    let mut copies: Vec<Rule> = Vec::new();
+    for defining_rule in program_rules {
+        let defining_rule: Rule = rename_apart(defining_rule, &fresh_prefix);
+        for head_atom in defining_rule.head() {
+            if head_atom.predicate() != predicate {
+                continue;
+            }
+            let mut unifier: Unifier = Unifier::new(fresh_prefix.clone());
+            let mut unifies: bool = true;
+            for (term, head_term) in atom.terms().zip(head_atom.terms()) {
+                unifies &= unifier.unify(term, head_term)?;
+            }
+            if !unifies {
+                // The defining rule never derives a tuple that matches the atom
+                continue;
+            }
+            let mut body: Vec<Literal> = rule.body()[..literal_index].to_vec();
+            body.extend(defining_rule.body().iter().cloned());
+            body.extend(rule.body()[literal_index + 1..].iter().cloned());
+            let mut copy: Rule = rule.clone();
+            *copy.body_mut() = body;
+            copies.push(apply_unifier(copy, &unifier)?);
+        }
+    }
+    match copies.is_empty() {
+        // Dropping the rule would also drop relations from the program
+        true => Err(UnfoldingError::NoMatch),
+        false => Ok(copies),
+    }
+}
+
+impl<'a, 'b> UnfoldRule<'a, 'b> {
+    /// The rules and positions of positive body atoms that can be unfolded
+    fn eligible_atoms(adg: &AnnotatedDependencyGraph) -> Vec<(String, usize)> {
+        let mut atoms: Vec<(String, usize)> = Vec::new();
+        for rule_name in adg.get_rule_names(true) {
+            let edges = adg.get_rule_edges(&rule_name);
+            if edges
+                .iter()
+                .any(|(_, _, rel_edge)| rel_edge.features.is_non_monotone())
+            {
+                continue;
+            }
+            for (body, _, rel_edge) in edges {
+                let atom: (String, usize) = (rule_name.clone(), rel_edge.body_index);
+                if rel_edge.sign == Sign::Positive
+                    && adg.is_defined_by_plain_rules(body)
+                    && !atoms.contains(&atom)
+                {
+                    atoms.push(atom);
+                }
+            }
+        }
+        atoms
+    }
+
+    /// Unfold the atom of a recorded step.
+    /// Returns None if the atom can't be unfolded (anymore).
+    pub fn replay(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        rule_name: String,
+        body_index: usize,
+        copy_names: Vec<String>,
+    ) -> Option<Self> {
+        if !Self::eligible_atoms(adg).contains(&(rule_name.clone(), body_index)) {
+            return None;
+        }
+        Some(Self {
+            adg,
+            _rng: rng,
+            rule_name,
+            body_index,
+            planned_copy_names: Some(copy_names),
+        })
+    }
+}
+
+impl<'a, 'b> MetamorphicTransformation<'a, 'b> for UnfoldRule<'a, 'b> {
+    fn new(
+        adg: &'a mut AnnotatedDependencyGraph,
+        rng: &'b mut rand_chacha::ChaCha8Rng,
+        _transformation_type: TransformationTypes,
+    ) -> Result<Option<Self>, MetamorphicError> {
+        let Some((rule_name, body_index)) = Self::eligible_atoms(adg).choose(rng).cloned() else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            adg,
+            _rng: rng,
+            rule_name,
+            body_index,
+            planned_copy_names: None,
+        }))
+    }
+
+    fn is_applicable(
+        adg: &AnnotatedDependencyGraph,
+        _transformation_type: TransformationTypes,
+    ) -> Result<bool, MetamorphicError> {
+        Ok(!Self::eligible_atoms(adg).is_empty())
+    }
+}
+
+impl<'a, 'b> ProgramTransformation for UnfoldRule<'a, 'b> {
+    fn apply(mut self, program: &ProgramHandle) -> Result<ProgramHandle, ValidationReport> {
+        let Some(rule) = util::fetch_rule_by_name(self.rule_name.clone(), program) else {
+            println!("Rule {} has vanished from the program", self.rule_name);
+            return program.fork_full().submit();
+        };
+        let mut copies: Vec<Rule> = match unfold(rule, self.body_index, program.rules()) {
+            Ok(copies) => copies,
+            Err(UnfoldingError::NoMatch) => {
+                println!(
+                    "No defining rule matches body atom {} of rule {}",
+                    self.body_index, self.rule_name
+                );
+                return program.fork_full().submit();
+            }
+            Err(UnfoldingError::UnsupportedTerm) => {
+                println!(
+                    "Body atom {} of rule {} has terms that can't be unfolded",
+                    self.body_index, self.rule_name
+                );
+                return program.fork_full().submit();
+            }
+        };
+        let copy_names: Vec<String> = match self.planned_copy_names.take() {
+            Some(copy_names) if copy_names.len() == copies.len() => copy_names,
+            Some(copy_names) => {
+                println!(
+                    "Expected {} copies of rule {}, but unfolding gives {}",
+                    copy_names.len(),
+                    self.rule_name,
+                    copies.len()
+                );
+                return program.fork_full().submit();
+            }
+            None => util::get_new_rule_names(program, copies.len()),
+        };
+        for (copy, copy_name) in copies.iter_mut().zip(&copy_names) {
+            copy.set_name(copy_name);
+        }
+
+        let new_program: ProgramHandle = util::replace_rule(program, &self.rule_name, &copies)?;
+
+        self.adg.remove_rule_edges(&self.rule_name);
+        for copy_name in &copy_names {
+            if let Some(copy) = util::fetch_rule_by_name(copy_name.clone(), &new_program) {
+                if let Err(error) = self.adg.add_rule_edges(copy) {
+                    self.adg.record_failed_update(format!(
+                        "Failed to add the edges of an unfolded rule to the ADG: {error}"
+                    ));
+                }
+            }
+        }
+        // Edges were removed, which can lower the annotations, so they are computed again
+        if let Err(error) = self.adg.calculate_ancestry_and_inverse_stratum() {
+            self.adg.record_failed_update(format!(
+                "Failed to update the ADG after unfolding a rule: {error}"
+            ));
+        }
+        let copies: Vec<String> = copies.iter().map(|copy| copy.to_string()).collect();
+        println!("Unfolded rule {} into {}", self.rule_name, copies.join(" "));
+        self.adg.record_step(TransformationStep::UnfoldRule {
+            rule_name: self.rule_name,
+            body_index: self.body_index,
+            copy_names,
+        });
+
+        Ok(new_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nemo::datavalues::AnyDataValue;
+    use nemo::rule_model::components::{
+        atom::Atom,
+        literal::Literal,
+        rule::Rule,
+        tag::Tag,
+        term::{
+            Term,
+            operation::{Operation, operation_kind::OperationKind},
+            primitive::{Primitive, variable::Variable},
+        },
+    };
+
+    use super::{UnfoldingError, unfold};
+
+    /// A variable for a name and the anonymous variable for `_`
+    fn variable(name: &str) -> Term {
+        match name {
+            "_" => Term::Primitive(Primitive::Variable(Variable::anonymous())),
+            _ => Term::universal_variable(name),
+        }
+    }
+
+    fn constant(value: i64) -> Term {
+        Term::ground(AnyDataValue::new_integer_from_i64(value))
+    }
+
+    fn atom(predicate: &str, terms: Vec<Term>) -> Atom {
+        Atom::new(Tag::new(predicate.to_string()), terms)
+    }
+
+    fn positive(predicate: &str, variables: &[&str]) -> Literal {
+        Literal::Positive(atom(
+            predicate,
+            variables.iter().map(|name| variable(name)).collect(),
+        ))
+    }
+
+    #[test]
+    fn repeated_variable_merges_the_head_variables() {
+        // r(?u, ?w) :- q(?u, ?u), s(?w) with q(?a, ?b) :- e(?a, ?b)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("u"), variable("w")])],
+            vec![positive("q", &["u", "u"]), positive("s", &["w"])],
+        );
+        let defining = Rule::new(
+            vec![atom("q", vec![variable("a"), variable("b")])],
+            vec![positive("e", &["a", "b"])],
+        );
+        let copies = unfold(&unfolded, 0, [&defining].into_iter()).expect("q(?u, ?u) unifies");
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].head(), unfolded.head());
+        assert_eq!(
+            copies[0].body(),
+            &vec![positive("e", &["u", "u"]), positive("s", &["w"])]
+        );
+    }
+
+    #[test]
+    fn clashing_constants_skip_the_defining_rule() {
+        // r(?x) :- q(?x, 1) with q(?a, 2) :- e(?a) and q(?a, 1) :- f(?a)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("x")])],
+            vec![Literal::Positive(atom(
+                "q",
+                vec![variable("x"), constant(1)],
+            ))],
+        );
+        let clashing = Rule::new(
+            vec![atom("q", vec![variable("a"), constant(2)])],
+            vec![positive("e", &["a"])],
+        );
+        let matching = Rule::new(
+            vec![atom("q", vec![variable("a"), constant(1)])],
+            vec![positive("f", &["a"])],
+        );
+        let copies = unfold(&unfolded, 0, [&clashing, &matching].into_iter())
+            .expect("the second rule matches");
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].body(), &vec![positive("f", &["x"])]);
+
+        assert!(matches!(
+            unfold(&unfolded, 0, [&clashing].into_iter()),
+            Err(UnfoldingError::NoMatch)
+        ));
+    }
+
+    #[test]
+    fn ground_term_in_an_operation_is_unsupported() {
+        // r(?y) :- q(3), s(?y) with q(?a) :- e(?a), ?a > 0
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("y")])],
+            vec![
+                Literal::Positive(atom("q", vec![constant(3)])),
+                positive("s", &["y"]),
+            ],
+        );
+        let defining = Rule::new(
+            vec![atom("q", vec![variable("a")])],
+            vec![
+                positive("e", &["a"]),
+                Literal::Operation(Operation::new(
+                    OperationKind::NumericGreaterthan,
+                    vec![variable("a"), constant(0)],
+                )),
+            ],
+        );
+        assert!(matches!(
+            unfold(&unfolded, 0, [&defining].into_iter()),
+            Err(UnfoldingError::UnsupportedTerm)
+        ));
+    }
+
+    #[test]
+    fn anonymous_variables_match_anything() {
+        // r(?x) :- q(?x, _) with q(?a, ?b) :- e(?a, ?b)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("x")])],
+            vec![positive("q", &["x", "_"])],
+        );
+        let defining = Rule::new(
+            vec![atom("q", vec![variable("a"), variable("b")])],
+            vec![positive("e", &["a", "b"])],
+        );
+        let copies = unfold(&unfolded, 0, [&defining].into_iter()).expect("q(?x, _) unifies");
+        assert_eq!(copies[0].body(), &vec![positive("e", &["x", "v_1"])]);
+
+        // r(?x) :- q(?x, 1) with q(?a, _) :- e(?a)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("x")])],
+            vec![Literal::Positive(atom(
+                "q",
+                vec![variable("x"), constant(1)],
+            ))],
+        );
+        let defining = Rule::new(
+            vec![atom("q", vec![variable("a"), variable("_")])],
+            vec![positive("e", &["a"])],
+        );
+        let copies = unfold(&unfolded, 0, [&defining].into_iter()).expect("q(?a, _) unifies");
+        assert_eq!(copies[0].body(), &vec![positive("e", &["x"])]);
+    }
+
+    #[test]
+    fn body_only_variables_stay_fresh() {
+        // r(?x, ?v_0) :- q(?x), s(?v_0) with q(?a) :- e(?a, ?b), f(?b)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("x"), variable("v_0")])],
+            vec![positive("q", &["x"]), positive("s", &["v_0"])],
+        );
+        let defining = Rule::new(
+            vec![atom("q", vec![variable("a")])],
+            vec![positive("e", &["a", "b"]), positive("f", &["b"])],
+        );
+        let copies = unfold(&unfolded, 0, [&defining].into_iter()).expect("q(?x) unifies");
+        assert_eq!(
+            copies[0].body(),
+            &vec![
+                positive("e", &["x", "vv_1"]),
+                positive("f", &["vv_1"]),
+                positive("s", &["v_0"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_head_atom_of_a_defining_rule_gives_a_copy() {
+        // r(?x) :- q(?x) with q(?a), s(?b), q(?b) :- e(?a, ?b)
+        let unfolded = Rule::new(
+            vec![atom("r", vec![variable("x")])],
+            vec![positive("q", &["x"])],
+        );
+        let defining = Rule::new(
+            vec![
+                atom("q", vec![variable("a")]),
+                atom("s", vec![variable("b")]),
+                atom("q", vec![variable("b")]),
+            ],
+            vec![positive("e", &["a", "b"])],
+        );
+        let copies = unfold(&unfolded, 0, [&defining].into_iter()).expect("both q atoms unify");
+        assert_eq!(copies.len(), 2);
+        assert_eq!(copies[0].body(), &vec![positive("e", &["x", "v_1"])]);
+        assert_eq!(copies[1].body(), &vec![positive("e", &["v_0", "x"])]);
+    }
+}
@@ -1,6 +1,17 @@
 use nemo::rule_model::{
-    components::{rule::Rule, statement::Statement},
-    programs::{ProgramRead, handle::ProgramHandle},
+    components::{
+        IterableVariables,
+        fact::Fact,
+        rule::Rule,
+        statement::Statement,
+        term::{
+            Term,
+            primitive::{Primitive, ground::GroundTerm},
+        },
+    },
+    error::ValidationReport,
+    pipeline::commit::ProgramCommit,
+    programs::{ProgramRead, ProgramWrite, handle::ProgramHandle},
 };
 
 pub fn fetch_rule_by_name(rule_name: String, program: &ProgramHandle) -> Option<&Rule> {
@@ -18,3 +29,76 @@ pub fn fetch_rule_by_name(rule_name: String, program: &ProgramHandle) -> Option<
     }
     None
 }
+
+/// Replace the rule with this name by the given rules, at its position in the program.
+/// Everything else is kept.
+pub fn replace_rule(
+    program: &ProgramHandle,
+    rule_name: &str,
+    replacement: &[Rule],
+) -> Result<ProgramHandle, ValidationReport> {
+    let mut commit: ProgramCommit = program.fork();
+    for statement in program.statements() {
+        match statement {
+            Statement::Rule(rule) if rule.name().as_deref() == Some(rule_name) => {
+                for new_rule in replacement {
+                    commit.add_rule(new_rule.clone());
+                }
+            }
+            _ => commit.keep(statement),
+        }
+    }
+    commit.submit()
+}
+
+/// Get a rule name of the form `r_<number>` that no rule of the program has yet
+pub fn get_new_rule_name(program: &ProgramHandle) -> String {
+    get_new_rule_names(program, 1).remove(0)
+}
+
+/// Get `count` distinct rule names of the form `r_<number>` that no rule of the program has yet
+pub fn get_new_rule_names(program: &ProgramHandle, count: usize) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut number: usize = program.statements().count();
+    while names.len() < count {
+        let name = format!("r_{number}");
+        if fetch_rule_by_name(name.clone(), program).is_none() {
+            names.push(name);
+        }
+        number += 1;
+    }
+    names
+}
+
+/// A prefix `p` such that no variable of the rule is named `p_...`,
+/// so variables `p_0`, `p_1`, ... are fresh in the rule
+pub fn fresh_variable_prefix(rule: &Rule) -> String {
+    let mut prefix: String = String::from("v");
+    while rule.variables().any(|variable| {
+        variable
+            .name()
+            .is_some_and(|name| name.starts_with(&format!("{prefix}_")))
+    }) {
+        prefix.push('v');
+    }
+    prefix
+}
+
+/// The terms of a fact, None if one of them is not ground
+pub fn fact_ground_terms(fact: &Fact) -> Option<Vec<GroundTerm>> {
+    fact.terms()
+        .map(|term| match term {
+            Term::Primitive(Primitive::Ground(gt)) => Some(gt.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether both tuples consist of the same values
+pub fn same_terms(terms: &[GroundTerm], other_terms: &[GroundTerm]) -> bool {
+    terms.len() == other_terms.len()
+        && terms
+            .iter()
+            .zip(other_terms)
+            .all(|(gt, other_gt)| gt.value() == other_gt.value())
+}